@@ -0,0 +1,118 @@
+// Times the optimizer on random inventories of realistic sizes. Run with `cargo bench`
+use std::time::{Duration, Instant};
+use wuwa_calculator::calculator::{BaseStats, Enemy, Scaling, Stats, Target};
+use wuwa_calculator::echo::{Echo, StatType};
+use wuwa_calculator::optimizer::{optimize_team, optimize_with, OptimizeOptions, TeamMember};
+use wuwa_calculator::sonata::Sonata;
+use wuwa_calculator::{Element, SkillType};
+
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn pick<T: Copy>(&mut self, items: &[T]) -> T {
+        items[(self.next() % items.len() as u64) as usize]
+    }
+}
+
+fn random_inventory(seed: u64, size: usize) -> Vec<Echo> {
+    const SUB_STATS: [(StatType, [f64; 4]); 10] = [
+        (StatType::AtkFlat, [30.0, 40.0, 50.0, 60.0]),
+        (StatType::AtkMult, [0.064, 0.079, 0.094, 0.116]),
+        (StatType::HpFlat, [320.0, 390.0, 470.0, 580.0]),
+        (StatType::DefFlat, [40.0, 50.0, 60.0, 70.0]),
+        (StatType::CritRate, [0.063, 0.075, 0.087, 0.105]),
+        (StatType::CritDmg, [0.126, 0.150, 0.174, 0.210]),
+        (StatType::EnergyRegen, [0.068, 0.084, 0.100, 0.124]),
+        (StatType::BasicDmg, [0.064, 0.079, 0.094, 0.116]),
+        (StatType::HeavyDmg, [0.064, 0.079, 0.094, 0.116]),
+        (StatType::LiberationDmg, [0.064, 0.079, 0.094, 0.116]),
+    ];
+    const SONATAS: [Sonata; 4] = [Sonata::None, Sonata::SierraGale, Sonata::LingeringTunes, Sonata::MoltenRift];
+
+    let mut rng = Rng(seed);
+    (0..size)
+        .map(|_| {
+            let (cost, main_stats, secondary_stat): (isize, &[(StatType, f64)], (StatType, f64)) = match rng.next() % 10 {
+                0..=1 => (4, &[(StatType::CritRate, 0.22), (StatType::CritDmg, 0.44), (StatType::AtkMult, 0.33)], (StatType::AtkFlat, 150.0)),
+                2..=4 => (3, &[(StatType::AtkMult, 0.30), (StatType::EnergyRegen, 0.32), (StatType::AeroDmg, 0.30)], (StatType::AtkFlat, 100.0)),
+                _ => (1, &[(StatType::AtkMult, 0.18), (StatType::HpMult, 0.228)], (StatType::HpFlat, 2280.0)),
+            };
+            let (main_stat_type, main_stat_value) = rng.pick(main_stats);
+            let mut sub_stat_types = SUB_STATS.to_vec();
+            let sub_stats = [(); 5].map(|_| {
+                let (stat_type, tiers) = sub_stat_types.swap_remove((rng.next() % sub_stat_types.len() as u64) as usize);
+                (stat_type, rng.pick(&tiers))
+            });
+            Echo {
+                cost,
+                rarity: 5,
+                sonata: rng.pick(&SONATAS),
+                identity: None,
+                main_stat_type,
+                main_stat_value,
+                secondary_stat_type: secondary_stat.0,
+                secondary_stat_value: secondary_stat.1,
+                sub_stats,
+            }
+        })
+        .collect()
+}
+
+// Runs `run` a few times and returns the fastest time, which is the least affected by other processes
+fn time(run: impl Fn()) -> Duration {
+    (0..3)
+        .map(|_| {
+            let start = Instant::now();
+            run();
+            start.elapsed()
+        })
+        .min()
+        .unwrap()
+}
+
+fn main() {
+    let stats = Stats::new_from_base(BaseStats {hp: 10000.0, atk: 400.0, def: 1000.0});
+    let target = Target {
+        element: Element::Aero,
+        skill_type: SkillType::Basic,
+        skill_multiplier: 1.0,
+        skill_scaling_bonus: 1.0,
+        scaling: Scaling::ATK,
+    };
+    let enemy = Enemy::new(90);
+
+    for size in [100, 300, 500] {
+        let echoes = random_inventory(42, size);
+        for (name, options) in [("1 thread", OptimizeOptions::default()), ("parallel", OptimizeOptions::parallel())] {
+            let elapsed = time(|| {
+                optimize_with(stats.clone(), &echoes, target, 90, enemy, &options).unwrap();
+            });
+            println!("optimize_with, {} echoes, {}: {:?}", size, name, elapsed);
+        }
+    }
+
+    let heavy = Target {skill_type: SkillType::Heavy, ..target};
+    let liberation = Target {skill_type: SkillType::Liberation, scaling: Scaling::HP, ..target};
+    let members = [(vec![(target, 2.0), (heavy, 1.0)], 2.0), (vec![(heavy, 3.0)], 1.0), (vec![(liberation, 1.0)], 1.0)]
+        .map(|(rotation, weight)| TeamMember {
+            stats: stats.clone(),
+            rotation,
+            character_level: 90,
+            weight,
+            options: OptimizeOptions::default(),
+        });
+    for size in [150, 300] {
+        let echoes = random_inventory(42, size);
+        let elapsed = time(|| {
+            optimize_team(&members, &echoes, enemy).unwrap();
+        });
+        println!("optimize_team, 3 members, {} echoes: {:?}", size, elapsed);
+    }
+}
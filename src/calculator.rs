@@ -4,12 +4,82 @@ use crate::{Element, SkillType};
 mod tests;
 
 /// The `Target` struct holds all the information about an attack needed to calculate its damage
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, PartialEq, Debug)]
 pub struct Target {
     pub element: Element,
     pub skill_type: SkillType,
     pub skill_multiplier: f64,
     pub skill_scaling_bonus: f64,
+    pub scaling: Scaling,
+}
+
+/// The `Scaling` struct holds how much of each stat a skill scales off. Skills that scale off a mix of
+/// stats have a separate multiplier for each of them
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Scaling {
+    pub atk: f64,
+    pub hp: f64,
+    pub def: f64,
+}
+
+impl Scaling {
+    /// Scaling of a skill that scales fully off ATK
+    pub const ATK: Scaling = Scaling {atk: 1.0, hp: 0.0, def: 0.0};
+    /// Scaling of a skill that scales fully off HP
+    pub const HP: Scaling = Scaling {atk: 0.0, hp: 1.0, def: 0.0};
+    /// Scaling of a skill that scales fully off DEF
+    pub const DEF: Scaling = Scaling {atk: 0.0, hp: 0.0, def: 1.0};
+}
+
+/// The `HealTarget` struct holds all the information about a heal or shield needed to calculate its amount
+#[derive(Clone, Copy)]
+pub struct HealTarget {
+    pub multiplier: f64,
+    pub flat: f64,
+    pub scaling: Scaling,
+}
+
+/// The `Enemy` struct holds all the information about an enemy needed to calculate the damage dealt to it
+#[derive(Clone, Copy)]
+pub struct Enemy {
+    pub level: isize,
+    /// Base resistance per element, indexed by `Element`. Negative values are weaknesses
+    pub resistance: [f64; 6],
+    /// Overrides the DEF derived from the enemy's level
+    pub def: Option<f64>,
+    /// Fraction of incoming damage reduced, regardless of element
+    pub dmg_reduction: f64,
+}
+
+impl Enemy {
+    /// Resistance value for an element the enemy is immune to
+    pub const IMMUNE: f64 = f64::INFINITY;
+
+    /// Creates a new `Enemy` of the given level with the default 10% resistance to every element
+    ///
+    /// # Examples
+    /// ```
+    /// use wuwa_calculator::Element;
+    /// use wuwa_calculator::calculator::Enemy;
+    ///
+    /// let mut enemy = Enemy::new(90);
+    /// enemy.resistance[Element::Havoc as usize] = Enemy::IMMUNE;
+    /// enemy.resistance[Element::Aero as usize] = -0.2;
+    /// assert_eq!(enemy.def(), 1512.0);
+    /// ```
+    pub fn new(level: isize) -> Enemy {
+        Enemy {
+            level,
+            resistance: [0.1; 6],
+            def: None,
+            dmg_reduction: 0.0,
+        }
+    }
+
+    /// Returns the DEF of the enemy
+    pub fn def(&self) -> f64 {
+        self.def.unwrap_or(8.0 * self.level as f64 + 792.0)
+    }
 }
 
 /// The `BaseStats` struct holds the base stats of a character, which are used to initialize the `Stats` struct
@@ -45,6 +115,12 @@ pub struct Stats {
     pub skill_dmg: [f64; 4],
 
     pub healing_bonus: f64,
+
+    pub element_deepen: [f64; 6],
+    pub skill_deepen: [f64; 4],
+
+    pub def_ignore: f64,
+    pub res_pen: [f64; 6],
 }
 
 impl Stats {
@@ -80,6 +156,12 @@ impl Stats {
             skill_dmg: [0.0; 4],
 
             healing_bonus: 1.0,
+
+            element_deepen: [0.0; 6],
+            skill_deepen: [0.0; 4],
+
+            def_ignore: 0.0,
+            res_pen: [0.0; 6],
         }
     }
 
@@ -93,77 +175,111 @@ impl Stats {
         self.base_atk * self.atk_mult + self.atk_flat
     }
 
-    /// Returns the DEF of the character
-    pub fn def(&self) -> f64 { // TODO fix this
+    /// Returns the DEF of the character. Like HP and ATK, DEF% only scales the base DEF, and flat DEF is added after
+    pub fn def(&self) -> f64 {
         self.base_def * self.def_mult + self.def_flat
     }
 
-    fn hit_multiplier_noncrit(&self) -> f64 { // TODO deepen
-        self.atk()
+    /// Returns the fraction of an enemy's damage the character takes after DEF mitigation
+    pub fn incoming_damage_multiplier(&self, enemy: Enemy) -> f64 {
+        let enemy_level_part = 800.0 + 8.0 * enemy.level as f64;
+        enemy_level_part / (enemy_level_part + self.def())
+    }
+
+    /// Returns the effective HP of the character against an enemy, which is the raw damage the enemy needs
+    /// to deal to take the character down
+    ///
+    /// # Examples
+    /// ```
+    /// use wuwa_calculator::calculator::{BaseStats, Enemy, Stats};
+    ///
+    /// let stats = Stats::new_from_base(BaseStats {hp: 10000.0, atk: 400.0, def: 1520.0});
+    /// assert_eq!(stats.effective_hp(Enemy::new(90)), 20000.0);
+    /// ```
+    pub fn effective_hp(&self, enemy: Enemy) -> f64 {
+        self.hp() / self.incoming_damage_multiplier(enemy)
     }
 
-    fn hit_multiplier_crit(&self) -> f64 { // TODO deepen
-        self.atk() * self.crit_dmg
+    /// Returns the value of the stats a skill scales off
+    pub fn scaling_value(&self, scaling: Scaling) -> f64 {
+        scaling.atk * self.atk() + scaling.hp * self.hp() + scaling.def * self.def()
     }
 
-    fn hit_multiplier_average(&self) -> f64 { // TODO deepen
-        self.atk() * (1.0 + self.crit_rate * (self.crit_dmg - 1.0))
+    fn hit_multiplier_noncrit(&self, scaling: Scaling) -> f64 {
+        self.scaling_value(scaling)
+    }
+
+    fn hit_multiplier_crit(&self, scaling: Scaling) -> f64 {
+        self.scaling_value(scaling) * self.crit_dmg
+    }
+
+    fn hit_multiplier_average(&self, scaling: Scaling) -> f64 {
+        self.scaling_value(scaling) * (1.0 + self.crit_rate * (self.crit_dmg - 1.0))
+    }
+
+    fn dmg_bonus_multiplier(&self, target: Target) -> f64 {
+        1.0 + self.element_dmg[target.element as usize] + self.skill_dmg[target.skill_type as usize]
+    }
+
+    // Deepen (amplify) bonuses are additive with each other, but multiply after the DMG bonus
+    fn deepen_multiplier(&self, target: Target) -> f64 {
+        1.0 + self.element_deepen[target.element as usize] + self.skill_deepen[target.skill_type as usize]
     }
 
     /// Returns the base damage of a skill (when it does not crit) without taking into account the enemy's resistance
     pub fn skill_base_damage_noncrit(&self, target: Target) -> f64 {
-        target.skill_multiplier * target.skill_scaling_bonus * self.hit_multiplier_noncrit()
-            * (1.0 + self.element_dmg[target.element as usize] + self.skill_dmg[target.skill_type as usize])
+        target.skill_multiplier * target.skill_scaling_bonus * self.hit_multiplier_noncrit(target.scaling)
+            * self.dmg_bonus_multiplier(target) * self.deepen_multiplier(target)
     }
 
     /// Returns the base damage of a skill (when it crits) without taking into account the enemy's resistance
     pub fn skill_base_damage_crit(&self, target: Target) -> f64 {
-        target.skill_multiplier * target.skill_scaling_bonus * self.hit_multiplier_crit()
-            * (1.0 + self.element_dmg[target.element as usize] + self.skill_dmg[target.skill_type as usize])
+        target.skill_multiplier * target.skill_scaling_bonus * self.hit_multiplier_crit(target.scaling)
+            * self.dmg_bonus_multiplier(target) * self.deepen_multiplier(target)
     }
 
     /// Returns the base damage of a skill (averaging crit and noncrit) without taking into account the enemy's resistance
     pub fn skill_base_damage_average(&self, target: Target) -> f64 {
-        target.skill_multiplier * target.skill_scaling_bonus * self.hit_multiplier_average()
-            * (1.0 + self.element_dmg[target.element as usize] + self.skill_dmg[target.skill_type as usize])
+        target.skill_multiplier * target.skill_scaling_bonus * self.hit_multiplier_average(target.scaling)
+            * self.dmg_bonus_multiplier(target) * self.deepen_multiplier(target)
     }
 
-    fn enemy_resistance(&self, character_level: isize, enemy_level: isize) -> f64 {
-        let def_ignore = 0.0; // TODO
-        let res_pen = 0.0; // TODO
-        let ele_res = 1.0; // TODO
-        let dmg_reduction = 1.0; // TODO
-
-        let base_res = 0.1;
-        let res_total = base_res + res_pen;
+    fn enemy_resistance(&self, element: Element, character_level: isize, enemy: Enemy) -> f64 {
+        let res_total = enemy.resistance[element as usize] - self.res_pen[element as usize];
         let res_adjusted = match res_total {
             ..= 0.0 => 1.0 - res_total/2.0,
             0.0 ..= 0.8 => 1.0 - res_total,
             _ => 1.0 / (1.0 + 5.0 * res_total)
         };
 
-        let enemy_def = 8.0 * enemy_level as f64 + 792.0;
         let character_level_part = 800.0 + 8.0 * character_level as f64;
-        let def_mult = character_level_part / (character_level_part + enemy_def * (1.0 - def_ignore));
+        let def_mult = character_level_part / (character_level_part + enemy.def() * (1.0 - self.def_ignore));
 
-        res_adjusted * ele_res * def_mult * dmg_reduction
+        res_adjusted * def_mult * (1.0 - enemy.dmg_reduction)
     }
-    
-    // TODO take in enemy resistance as an argument
 
     /// Returns the adjusted damage of a skill (when it does not crit) taking into account the enemy's resistance
-    pub fn skill_adjusted_damage_noncrit(&self, target: Target, character_level: isize, enemy_level: isize) -> f64 {
-        self.skill_base_damage_noncrit(target) * self.enemy_resistance(character_level, enemy_level)
+    pub fn skill_adjusted_damage_noncrit(&self, target: Target, character_level: isize, enemy: Enemy) -> f64 {
+        self.skill_base_damage_noncrit(target) * self.enemy_resistance(target.element, character_level, enemy)
     }
 
     /// Returns the adjusted damage of a skill (when it crits) taking into account the enemy's resistance
-    pub fn skill_adjusted_damage_crit(&self, target: Target, character_level: isize, enemy_level: isize) -> f64 {
-        self.skill_base_damage_crit(target) * self.enemy_resistance(character_level, enemy_level)
+    pub fn skill_adjusted_damage_crit(&self, target: Target, character_level: isize, enemy: Enemy) -> f64 {
+        self.skill_base_damage_crit(target) * self.enemy_resistance(target.element, character_level, enemy)
     }
 
     /// Returns the adjusted damage of a skill (averaging crit and noncrit) taking into account the enemy's resistance
-    pub fn skill_adjusted_damage_average(&self, target: Target, character_level: isize, enemy_level: isize) -> f64 {
-        self.skill_base_damage_average(target) * self.enemy_resistance(character_level, enemy_level)
+    pub fn skill_adjusted_damage_average(&self, target: Target, character_level: isize, enemy: Enemy) -> f64 {
+        self.skill_base_damage_average(target) * self.enemy_resistance(target.element, character_level, enemy)
+    }
+
+    /// Returns the amount healed by a heal, taking into account the character's healing bonus
+    pub fn heal_amount(&self, heal: HealTarget) -> f64 {
+        (heal.multiplier * self.scaling_value(heal.scaling) + heal.flat) * self.healing_bonus
     }
-}
 
+    /// Returns the amount of damage absorbed by a shield
+    pub fn shield_amount(&self, shield: HealTarget) -> f64 {
+        shield.multiplier * self.scaling_value(shield.scaling) + shield.flat
+    }
+}
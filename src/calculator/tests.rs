@@ -1,26 +1,31 @@
-use crate::calculator::{BaseStats, Stats, Target};
+use crate::character::{Character, JIYAN};
+use crate::calculator::{BaseStats, Enemy, HealTarget, Scaling, Stats, Target};
 use crate::{Element, SkillType};
 use crate::echo::{Echo, StatType};
+use crate::sonata::{add_set_bonuses, Sonata};
+use crate::weapon::{Weapon, HELIOS_CLEAVER, VERDANT_SUMMIT};
 
 // This is the tolerance for comparisons to numbers taken from the game.
 const TOLERANCE: f64 = 1.0;
 
 #[test]
 fn test_calculate_base_damage_set1() {
-    let jiyan_base_stats_70 = BaseStats {hp: 7954.0, atk: 343.0, def: 899.0};
-    let mut stats = Stats::new_from_base(jiyan_base_stats_70);
-
-    // Helios Cleaver lvl 70/70
-    stats.base_atk += 312.0;
-    stats.atk_mult += 0.251;
+    let jiyan = Character {
+        data: &JIYAN,
+        level: 70,
+        ascension: 4,
+        minor_forte: [true, true, false, false, false, false, false, false],
+    };
+    let mut stats = jiyan.stats().unwrap();
 
-    // Talent stats
-    stats.crit_rate += 0.012 + 0.012;
-    stats.crit_dmg += 0.12;
+    Weapon::new(&HELIOS_CLEAVER, 70, 4, 1).unwrap().add_to_stats(&mut stats, false);
 
     let echoes = [
         Echo {
             cost: 4,
+            rarity: 5,
+            sonata: Sonata::SierraGale,
+            identity: None,
             main_stat_type: StatType::CritRate,
             main_stat_value: 0.22,
             secondary_stat_type: StatType::AtkFlat,
@@ -35,6 +40,9 @@ fn test_calculate_base_damage_set1() {
         },
         Echo {
             cost: 3,
+            rarity: 5,
+            sonata: Sonata::SierraGale,
+            identity: None,
             main_stat_type: StatType::AeroDmg,
             main_stat_value: 0.252,
             secondary_stat_type: StatType::AtkFlat,
@@ -49,6 +57,9 @@ fn test_calculate_base_damage_set1() {
         },
         Echo {
             cost: 3,
+            rarity: 5,
+            sonata: Sonata::None,
+            identity: None,
             main_stat_type: StatType::AeroDmg,
             main_stat_value: 0.223,
             secondary_stat_type: StatType::AtkFlat,
@@ -63,6 +74,9 @@ fn test_calculate_base_damage_set1() {
         },
         Echo {
             cost: 1,
+            rarity: 5,
+            sonata: Sonata::None,
+            identity: None,
             main_stat_type: StatType::AtkMult,
             main_stat_value: 0.18,
             secondary_stat_type: StatType::HpFlat,
@@ -77,6 +91,9 @@ fn test_calculate_base_damage_set1() {
         },
         Echo {
             cost: 1,
+            rarity: 5,
+            sonata: Sonata::None,
+            identity: None,
             main_stat_type: StatType::AtkMult,
             main_stat_value: 0.18,
             secondary_stat_type: StatType::HpFlat,
@@ -97,16 +114,10 @@ fn test_calculate_base_damage_set1() {
     echoes[3].add_to_stats(&mut stats);
     echoes[4].add_to_stats(&mut stats);
 
-    // Echo set bonuses
-    stats.element_dmg[Element::Aero as usize] += 0.10;
+    add_set_bonuses(&echoes, &mut stats, false);
 
-    // First hit of his basic attack
-    let target = Target {
-        element: Element::Aero,
-        skill_type: SkillType::Basic,
-        skill_multiplier: 0.5354,
-        skill_scaling_bonus: 1.0,
-    };
+    // First hit of his basic attack, at level 6
+    let target = jiyan.skill("Basic Attack Stage 1", 6).unwrap();
     
     // Game data
     let expected_wildlife_dmgs = (1296.0, 2519.0);
@@ -122,31 +133,29 @@ fn test_calculate_base_damage_set1() {
     assert!((stats.skill_base_damage_crit(target) - expected_wildlife_dmgs.1).abs() < TOLERANCE);
 
     for (level, expected_dmgs) in expected_dmgs_per_level.iter() {
-        assert!((stats.skill_adjusted_damage_noncrit(target, 70, *level) - expected_dmgs.0).abs() < TOLERANCE);
-        assert!((stats.skill_adjusted_damage_crit(target, 70, *level) - expected_dmgs.1).abs() < TOLERANCE);
+        assert!((stats.skill_adjusted_damage_noncrit(target, 70, Enemy::new(*level)) - expected_dmgs.0).abs() < TOLERANCE);
+        assert!((stats.skill_adjusted_damage_crit(target, 70, Enemy::new(*level)) - expected_dmgs.1).abs() < TOLERANCE);
     }
 }
 
 #[test]
 fn test_calculate_base_damage_set2() {
-    let jiyan_base_stats_70 = BaseStats {hp: 7954.0, atk: 343.0, def: 899.0};
-    let mut stats = Stats::new_from_base(jiyan_base_stats_70);
-
-    // Verdant Summit 60/60
-    stats.base_atk += 374.0;
-    stats.crit_dmg += 0.359;
-    for i in 0..6 { // weapon passive
-        stats.element_dmg[i] += 0.12;
-    }
+    let jiyan = Character {
+        data: &JIYAN,
+        level: 70,
+        ascension: 4,
+        minor_forte: [true, true, true, true, false, false, false, false],
+    };
+    let mut stats = jiyan.stats().unwrap();
 
-    // Talent stats
-    stats.crit_rate += 0.012 + 0.012;
-    stats.atk_mult += 0.018 + 0.018;
-    stats.crit_dmg += 0.12;
+    Weapon::new(&VERDANT_SUMMIT, 60, 3, 1).unwrap().add_to_stats(&mut stats, false);
 
     let echoes = [
         Echo {
             cost: 4,
+            rarity: 5,
+            sonata: Sonata::SierraGale,
+            identity: None,
             main_stat_type: StatType::CritRate,
             main_stat_value: 0.22,
             secondary_stat_type: StatType::AtkFlat,
@@ -161,6 +170,9 @@ fn test_calculate_base_damage_set2() {
         },
         Echo {
             cost: 3,
+            rarity: 5,
+            sonata: Sonata::LingeringTunes,
+            identity: None,
             main_stat_type: StatType::EnergyRegen,
             main_stat_value: 0.268,
             secondary_stat_type: StatType::AtkFlat,
@@ -175,6 +187,9 @@ fn test_calculate_base_damage_set2() {
         },
         Echo {
             cost: 3,
+            rarity: 5,
+            sonata: Sonata::SierraGale,
+            identity: None,
             main_stat_type: StatType::AeroDmg,
             main_stat_value: 0.30,
             secondary_stat_type: StatType::AtkFlat,
@@ -189,6 +204,9 @@ fn test_calculate_base_damage_set2() {
         },
         Echo {
             cost: 1,
+            rarity: 5,
+            sonata: Sonata::LingeringTunes,
+            identity: None,
             main_stat_type: StatType::AtkMult,
             main_stat_value: 0.151,
             secondary_stat_type: StatType::HpFlat,
@@ -203,6 +221,9 @@ fn test_calculate_base_damage_set2() {
         },
         Echo {
             cost: 1,
+            rarity: 5,
+            sonata: Sonata::None,
+            identity: None,
             main_stat_type: StatType::AtkMult,
             main_stat_value: 0.122,
             secondary_stat_type: StatType::HpFlat,
@@ -223,17 +244,10 @@ fn test_calculate_base_damage_set2() {
     echoes[3].add_to_stats(&mut stats);
     echoes[4].add_to_stats(&mut stats);
 
-    // Echo set bonuses
-    stats.atk_mult += 0.10;
-    stats.element_dmg[Element::Aero as usize] += 0.10;
+    add_set_bonuses(&echoes, &mut stats, false);
 
-    // First hit of his basic attack
-    let target = Target {
-        element: Element::Aero,
-        skill_type: SkillType::Basic,
-        skill_multiplier: 0.5007,
-        skill_scaling_bonus: 1.0,
-    };
+    // First hit of his basic attack, at level 5
+    let target = jiyan.skill("Basic Attack Stage 1", 5).unwrap();
 
     // Game data
     let expected_wildfile_dmgs = (1353.0, 3743.0);
@@ -250,7 +264,185 @@ fn test_calculate_base_damage_set2() {
     assert!((stats.skill_base_damage_crit(target) - expected_wildfile_dmgs.1).abs() < TOLERANCE);
 
     for (level, expected_dmgs) in expected_dmgs_per_level.iter() {
-        assert!((stats.skill_adjusted_damage_noncrit(target, 70, *level) - expected_dmgs.0).abs() < TOLERANCE);
-        assert!((stats.skill_adjusted_damage_crit(target, 70, *level) - expected_dmgs.1).abs() < TOLERANCE);
+        assert!((stats.skill_adjusted_damage_noncrit(target, 70, Enemy::new(*level)) - expected_dmgs.0).abs() < TOLERANCE);
+        assert!((stats.skill_adjusted_damage_crit(target, 70, Enemy::new(*level)) - expected_dmgs.1).abs() < TOLERANCE);
     }
 }
+
+#[test]
+fn test_def_ignore_and_res_pen() {
+    let jiyan_base_stats_70 = BaseStats {hp: 7954.0, atk: 343.0, def: 899.0};
+    let mut stats = Stats::new_from_base(jiyan_base_stats_70);
+
+    let target = Target {
+        element: Element::Aero,
+        skill_type: SkillType::Basic,
+        skill_multiplier: 1.0,
+        skill_scaling_bonus: 1.0,
+        scaling: Scaling::ATK,
+    };
+    let base_damage = stats.skill_base_damage_noncrit(target);
+
+    // 10% base resistance and a level 90 enemy against a level 90 character
+    let def_mult = 1520.0 / (1520.0 + 1512.0);
+    let adjusted = stats.skill_adjusted_damage_noncrit(target, 90, Enemy::new(90));
+    assert!((adjusted - base_damage * 0.9 * def_mult).abs() < 1e-9);
+
+    // Penetrating exactly the base resistance removes the resistance multiplier
+    StatType::AeroResPen.add_to_stats(&mut stats, 0.1);
+    let adjusted = stats.skill_adjusted_damage_noncrit(target, 90, Enemy::new(90));
+    assert!((adjusted - base_damage * def_mult).abs() < 1e-9);
+
+    // Penetration of a different element has no effect
+    StatType::GlacioResPen.add_to_stats(&mut stats, 0.5);
+    let adjusted = stats.skill_adjusted_damage_noncrit(target, 90, Enemy::new(90));
+    assert!((adjusted - base_damage * def_mult).abs() < 1e-9);
+
+    // Negative resistance is halved
+    StatType::AeroResPen.add_to_stats(&mut stats, 0.2);
+    let adjusted = stats.skill_adjusted_damage_noncrit(target, 90, Enemy::new(90));
+    assert!((adjusted - base_damage * 1.1 * def_mult).abs() < 1e-9);
+
+    // Ignoring half of the enemy's DEF
+    StatType::DefIgnore.add_to_stats(&mut stats, 0.5);
+    let adjusted = stats.skill_adjusted_damage_noncrit(target, 90, Enemy::new(90));
+    assert!((adjusted - base_damage * 1.1 * (1520.0 / (1520.0 + 756.0))).abs() < 1e-9);
+}
+
+#[test]
+fn test_enemy_resistances() {
+    let jiyan_base_stats_70 = BaseStats {hp: 7954.0, atk: 343.0, def: 899.0};
+    let stats = Stats::new_from_base(jiyan_base_stats_70);
+
+    let target = Target {
+        element: Element::Aero,
+        skill_type: SkillType::Basic,
+        skill_multiplier: 1.0,
+        skill_scaling_bonus: 1.0,
+        scaling: Scaling::ATK,
+    };
+    let base_damage = stats.skill_base_damage_noncrit(target);
+    let def_mult = 1520.0 / (1520.0 + 1512.0);
+
+    let mut enemy = Enemy::new(90);
+    enemy.resistance[Element::Aero as usize] = 0.4;
+    let adjusted = stats.skill_adjusted_damage_noncrit(target, 90, enemy);
+    assert!((adjusted - base_damage * 0.6 * def_mult).abs() < 1e-9);
+
+    // Resistances above 80% have diminishing returns
+    enemy.resistance[Element::Aero as usize] = 1.0;
+    let adjusted = stats.skill_adjusted_damage_noncrit(target, 90, enemy);
+    assert!((adjusted - base_damage / 6.0 * def_mult).abs() < 1e-9);
+
+    enemy.resistance[Element::Aero as usize] = Enemy::IMMUNE;
+    assert_eq!(stats.skill_adjusted_damage_noncrit(target, 90, enemy), 0.0);
+
+    enemy.resistance[Element::Aero as usize] = 0.0;
+    enemy.dmg_reduction = 0.25;
+    let adjusted = stats.skill_adjusted_damage_noncrit(target, 90, enemy);
+    assert!((adjusted - base_damage * 0.75 * def_mult).abs() < 1e-9);
+
+    enemy.dmg_reduction = 0.0;
+    enemy.def = Some(0.0);
+    let adjusted = stats.skill_adjusted_damage_noncrit(target, 90, enemy);
+    assert!((adjusted - base_damage).abs() < 1e-9);
+}
+
+#[test]
+fn test_deepen_multiplies_after_dmg_bonus() {
+    let jiyan_base_stats_70 = BaseStats {hp: 7954.0, atk: 343.0, def: 899.0};
+    let mut stats = Stats::new_from_base(jiyan_base_stats_70);
+
+    let target = Target {
+        element: Element::Aero,
+        skill_type: SkillType::Liberation,
+        skill_multiplier: 1.0,
+        skill_scaling_bonus: 1.0,
+        scaling: Scaling::ATK,
+    };
+
+    StatType::AeroDmg.add_to_stats(&mut stats, 0.3);
+    StatType::LiberationDmg.add_to_stats(&mut stats, 0.2);
+    let base_damage = stats.skill_base_damage_noncrit(target);
+    assert!((base_damage - stats.atk() * 1.5).abs() < 1e-9);
+
+    // Element and skill deepen are additive within their own bucket
+    StatType::AeroDeepen.add_to_stats(&mut stats, 0.25);
+    StatType::LiberationDeepen.add_to_stats(&mut stats, 0.25);
+    assert!((stats.skill_base_damage_noncrit(target) - base_damage * 1.5).abs() < 1e-9);
+
+    // Deepen of a different element or skill type has no effect
+    StatType::HavocDeepen.add_to_stats(&mut stats, 0.5);
+    StatType::BasicDeepen.add_to_stats(&mut stats, 0.5);
+    assert!((stats.skill_base_damage_noncrit(target) - base_damage * 1.5).abs() < 1e-9);
+}
+
+#[test]
+fn test_hp_and_def_scaling() {
+    let base_stats = BaseStats {hp: 10000.0, atk: 400.0, def: 1000.0};
+    let mut stats = Stats::new_from_base(base_stats);
+    StatType::HpMult.add_to_stats(&mut stats, 0.5);
+    StatType::DefFlat.add_to_stats(&mut stats, 200.0);
+
+    let mut target = Target {
+        element: Element::Spectro,
+        skill_type: SkillType::Skill,
+        skill_multiplier: 0.1,
+        skill_scaling_bonus: 1.0,
+        scaling: Scaling::HP,
+    };
+    assert!((stats.skill_base_damage_noncrit(target) - 1500.0).abs() < 1e-9);
+
+    target.scaling = Scaling::DEF;
+    assert!((stats.skill_base_damage_noncrit(target) - 120.0).abs() < 1e-9);
+
+    // Skills that scale off several stats add up each part
+    target.scaling = Scaling {atk: 2.0, hp: 0.05, def: 0.0};
+    assert!((stats.skill_base_damage_noncrit(target) - (80.0 + 75.0)).abs() < 1e-9);
+}
+
+#[test]
+fn test_heal_and_shield_amount() {
+    let base_stats = BaseStats {hp: 10000.0, atk: 400.0, def: 1000.0};
+    let mut stats = Stats::new_from_base(base_stats);
+    StatType::Healing.add_to_stats(&mut stats, 0.2);
+
+    let heal = HealTarget {
+        multiplier: 0.1,
+        flat: 500.0,
+        scaling: Scaling::HP,
+    };
+    assert!((stats.heal_amount(heal) - 1500.0 * 1.2).abs() < 1e-9);
+
+    // Healing bonus does not apply to shields
+    let shield = HealTarget {
+        multiplier: 2.0,
+        flat: 300.0,
+        scaling: Scaling::DEF,
+    };
+    assert!((stats.shield_amount(shield) - 2300.0).abs() < 1e-9);
+}
+
+#[test]
+fn test_def_and_effective_hp() {
+    let jiyan_base_stats_70 = BaseStats {hp: 7954.0, atk: 343.0, def: 899.0};
+    let mut stats = Stats::new_from_base(jiyan_base_stats_70);
+    assert_eq!(stats.def(), 899.0);
+
+    // DEF% only applies to base DEF, flat DEF is added on top
+    StatType::DefMult.add_to_stats(&mut stats, 0.128);
+    StatType::DefFlat.add_to_stats(&mut stats, 50.0);
+    assert!((stats.def() - (899.0 * 1.128 + 50.0)).abs() < 1e-9);
+
+    // A level 70 enemy has a 1360 DEF constant
+    let enemy = Enemy::new(70);
+    let multiplier = 1360.0 / (1360.0 + stats.def());
+    assert!((stats.incoming_damage_multiplier(enemy) - multiplier).abs() < 1e-12);
+    assert!((stats.effective_hp(enemy) - stats.hp() / multiplier).abs() < 1e-9);
+
+    // More DEF always means more effective HP, and higher level enemies get through more of it
+    let effective_hp = stats.effective_hp(enemy);
+    StatType::DefMult.add_to_stats(&mut stats, 0.147);
+    assert!(stats.effective_hp(enemy) > effective_hp);
+    assert!(stats.effective_hp(Enemy::new(90)) < stats.effective_hp(enemy));
+}
@@ -0,0 +1,172 @@
+use std::error::Error;
+use std::fmt;
+use crate::{level_breakpoints, Element, SkillType};
+use crate::calculator::{BaseStats, Scaling, Stats, Target};
+use crate::echo::StatType;
+use crate::modifier::StatModifier;
+
+#[cfg(test)]
+mod tests;
+
+/// The `CharacterLevel` struct holds the base stats of a character at a level breakpoint.
+/// Stats between two breakpoints of the same ascension are interpolated
+#[derive(Copy, Clone)]
+pub struct CharacterLevel {
+    pub level: isize,
+    pub ascension: usize,
+    pub hp: f64,
+    pub atk: f64,
+    pub def: f64,
+}
+
+/// The `SkillTable` struct holds the multipliers of a single hit of a skill at the skill levels they are known for
+#[derive(Copy, Clone)]
+pub struct SkillTable {
+    pub name: &'static str,
+    pub skill_type: SkillType,
+    pub scaling: Scaling,
+    /// Pairs of a skill level and the multiplier at that level
+    pub multipliers: &'static [(usize, f64)],
+}
+
+/// The `CharacterData` struct holds everything about a character that doesn't depend on the player's progress
+pub struct CharacterData {
+    pub name: &'static str,
+    pub element: Element,
+    pub levels: &'static [CharacterLevel],
+    /// Bonuses of the minor forte nodes. The two tier 1 nodes of each stat come first, then the tier 2 nodes
+    pub minor_forte: [(StatType, f64); 8],
+    /// Stat bonuses of the inherent skills, which are assumed to be active
+    pub inherent: &'static [(StatType, f64)],
+    pub skills: &'static [SkillTable],
+}
+
+pub const JIYAN: CharacterData = CharacterData {
+    name: "Jiyan",
+    element: Element::Aero,
+    // Only the stats at 70/4 have been read from the character screen so far, so other levels are errors until
+    // their own breakpoints are added
+    levels: &[
+        CharacterLevel {level: 70, ascension: 4, hp: 7954.0, atk: 343.0, def: 899.0},
+    ],
+    minor_forte: [
+        (StatType::CritRate, 0.012),
+        (StatType::CritRate, 0.012),
+        (StatType::AtkMult, 0.018),
+        (StatType::AtkMult, 0.018),
+        (StatType::CritRate, 0.028),
+        (StatType::CritRate, 0.028),
+        (StatType::AtkMult, 0.042),
+        (StatType::AtkMult, 0.042),
+    ],
+    // Heavenly Balance
+    inherent: &[(StatType::CritDmg, 0.12)],
+    // Only the hits and skill levels read from the game are listed
+    skills: &[
+        SkillTable {
+            name: "Basic Attack Stage 1",
+            skill_type: SkillType::Basic,
+            scaling: Scaling::ATK,
+            multipliers: &[(5, 0.5007), (6, 0.5354)],
+        },
+    ],
+};
+
+/// The `CharacterError` enum represents the data a character is missing for what was asked of it
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum CharacterError {
+    /// The character's data has no breakpoints around the level at that ascension
+    UnknownLevel { level: isize, ascension: usize },
+    /// The character's data has no skill of that name
+    UnknownSkill,
+    /// The skill's multiplier at that skill level isn't in the character's data
+    UnknownSkillLevel(usize),
+}
+
+impl fmt::Display for CharacterError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CharacterError::UnknownLevel {level, ascension} => write!(f, "the base stats at level {} and ascension {} aren't known", level, ascension),
+            CharacterError::UnknownSkill => write!(f, "the character has no such skill"),
+            CharacterError::UnknownSkillLevel(skill_level) => write!(f, "the skill's multiplier at skill level {} isn't known", skill_level),
+        }
+    }
+}
+
+impl Error for CharacterError {}
+
+/// The `Character` struct represents a character at a given level and forte progress
+#[derive(Copy, Clone)]
+pub struct Character {
+    pub data: &'static CharacterData,
+    pub level: isize,
+    pub ascension: usize,
+    /// Which minor forte nodes are unlocked, in the same order as `CharacterData::minor_forte`
+    pub minor_forte: [bool; 8],
+}
+
+impl Character {
+    /// Returns the base stats of the character, or an error if its data has no breakpoints for its level and ascension
+    ///
+    /// # Examples
+    /// ```
+    /// use wuwa_calculator::character::{Character, JIYAN};
+    ///
+    /// let jiyan = Character {data: &JIYAN, level: 70, ascension: 4, minor_forte: [false; 8]};
+    /// let base_stats = jiyan.base_stats().unwrap();
+    /// assert_eq!(base_stats.atk, 343.0);
+    /// ```
+    pub fn base_stats(&self) -> Result<BaseStats, CharacterError> {
+        let (lower, upper, t) = level_breakpoints(self.data.levels, self.level, self.ascension, |point| (point.level, point.ascension))
+            .ok_or(CharacterError::UnknownLevel {level: self.level, ascension: self.ascension})?;
+        Ok(BaseStats {
+            hp: lower.hp + (upper.hp - lower.hp) * t,
+            atk: lower.atk + (upper.atk - lower.atk) * t,
+            def: lower.def + (upper.def - lower.def) * t,
+        })
+    }
+
+    /// Returns the stats of the character with its unlocked minor forte and inherent bonuses, before any weapon or echo
+    ///
+    /// # Examples
+    /// ```
+    /// use wuwa_calculator::character::{Character, JIYAN};
+    ///
+    /// let jiyan = Character {
+    ///     data: &JIYAN,
+    ///     level: 70,
+    ///     ascension: 4,
+    ///     minor_forte: [true, true, false, false, false, false, false, false],
+    /// };
+    /// let stats = jiyan.stats().unwrap();
+    /// assert_eq!(stats.crit_rate, 0.05 + 0.012 + 0.012);
+    /// ```
+    pub fn stats(&self) -> Result<Stats, CharacterError> {
+        let mut stats = Stats::new_from_base(self.base_stats()?);
+        for ((stat_type, value), unlocked) in self.data.minor_forte.iter().zip(self.minor_forte) {
+            if unlocked {
+                StatModifier::add(*stat_type, *value).apply(&mut stats);
+            }
+        }
+        for (stat_type, value) in self.data.inherent {
+            StatModifier::add(*stat_type, *value).apply(&mut stats);
+        }
+        Ok(stats)
+    }
+
+    /// Returns the target of a hit of one of the character's skills at the given skill level, or an error if the
+    /// character has no such skill or its multiplier at that level isn't known
+    pub fn skill(&self, name: &str, skill_level: usize) -> Result<Target, CharacterError> {
+        let skill = self.data.skills.iter().find(|skill| skill.name == name).ok_or(CharacterError::UnknownSkill)?;
+        let (_, multiplier) = skill.multipliers.iter()
+            .find(|(level, _)| *level == skill_level)
+            .ok_or(CharacterError::UnknownSkillLevel(skill_level))?;
+        Ok(Target {
+            element: self.data.element,
+            skill_type: skill.skill_type,
+            skill_multiplier: *multiplier,
+            skill_scaling_bonus: 1.0,
+            scaling: skill.scaling,
+        })
+    }
+}
@@ -0,0 +1,73 @@
+use crate::calculator::Scaling;
+use crate::{Element, SkillType};
+use crate::character::{Character, CharacterData, CharacterError, CharacterLevel, SkillTable, JIYAN};
+use crate::echo::StatType;
+
+#[test]
+fn test_character_levels_and_skills() {
+    const TEST_CHARACTER: CharacterData = CharacterData {
+        name: "Test",
+        element: Element::Spectro,
+        levels: &[
+            CharacterLevel {level: 60, ascension: 4, hp: 6000.0, atk: 300.0, def: 700.0},
+            CharacterLevel {level: 70, ascension: 4, hp: 7000.0, atk: 350.0, def: 800.0},
+            CharacterLevel {level: 70, ascension: 5, hp: 7400.0, atk: 370.0, def: 850.0},
+        ],
+        minor_forte: [(StatType::HpMult, 0.018); 8],
+        inherent: &[],
+        skills: &[
+            SkillTable {
+                name: "Basic Attack Stage 1",
+                skill_type: SkillType::Basic,
+                scaling: Scaling::HP,
+                multipliers: &[(1, 0.1), (2, 0.11), (3, 0.12)],
+            },
+        ],
+    };
+
+    let mut character = Character {
+        data: &TEST_CHARACTER,
+        level: 65,
+        ascension: 4,
+        minor_forte: [true, false, true, false, false, false, false, false],
+    };
+    let base_stats = character.base_stats().unwrap();
+    assert!((base_stats.hp - 6500.0).abs() < 1e-9);
+    assert!((base_stats.atk - 325.0).abs() < 1e-9);
+    assert!((base_stats.def - 750.0).abs() < 1e-9);
+    assert!((character.stats().unwrap().hp_mult - 1.036).abs() < 1e-9);
+
+    // Ascending changes the base stats at the same level
+    character.level = 70;
+    assert_eq!(character.base_stats().unwrap().hp, 7000.0);
+    character.ascension = 5;
+    assert_eq!(character.base_stats().unwrap().hp, 7400.0);
+    character.level = 75;
+    assert_eq!(character.base_stats().err(), Some(CharacterError::UnknownLevel {level: 75, ascension: 5}));
+
+    let target = character.skill("Basic Attack Stage 1", 2).unwrap();
+    assert_eq!(target.skill_multiplier, 0.11);
+    assert_eq!(character.skill("Basic Attack Stage 1", 4).err(), Some(CharacterError::UnknownSkillLevel(4)));
+    assert_eq!(character.skill("Basic Attack Stage 1", 0).err(), Some(CharacterError::UnknownSkillLevel(0)));
+    assert_eq!(character.skill("Heavy Attack", 1).err(), Some(CharacterError::UnknownSkill));
+}
+
+#[test]
+fn test_jiyan_levels_and_skills() {
+    let mut jiyan = Character {data: &JIYAN, level: 70, ascension: 4, minor_forte: [false; 8]};
+    let base_stats = jiyan.base_stats().unwrap();
+    assert_eq!((base_stats.hp, base_stats.atk, base_stats.def), (7954.0, 343.0, 899.0));
+    assert_eq!(jiyan.skill("Basic Attack Stage 1", 5).unwrap().skill_multiplier, 0.5007);
+    assert_eq!(jiyan.skill("Basic Attack Stage 1", 6).unwrap().skill_multiplier, 0.5354);
+
+    // Levels that weren't read from the game are errors instead of guesses
+    for (level, ascension) in [(1, 0), (60, 4), (71, 4), (70, 5), (90, 6)] {
+        jiyan.level = level;
+        jiyan.ascension = ascension;
+        assert_eq!(jiyan.base_stats().err(), Some(CharacterError::UnknownLevel {level, ascension}));
+        assert_eq!(jiyan.stats().err(), Some(CharacterError::UnknownLevel {level, ascension}));
+    }
+    for skill_level in [1, 4, 7, 10] {
+        assert_eq!(jiyan.skill("Basic Attack Stage 1", skill_level).err(), Some(CharacterError::UnknownSkillLevel(skill_level)));
+    }
+}
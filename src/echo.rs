@@ -1,7 +1,33 @@
-use crate::calculator::Stats;
+use std::error::Error;
+use std::fmt;
+use crate::{Element, SkillType};
+use crate::calculator::{Stats, Target};
+use crate::modifier::StatModifier;
+use crate::sonata::Sonata;
 
-/// The `StatType` enum represents the different types of stats that can be added to a character through an echo
-#[derive(Copy, Clone)]
+#[cfg(test)]
+mod tests;
+
+/// Maximum level of a 5-star echo
+pub const MAX_ECHO_LEVEL: isize = 25;
+
+/// Returns the maximum level of echoes of a rarity, in stars, or `None` if echoes can't have the rarity. Echoes go
+/// from 2 to 5 stars, and each star adds 5 levels
+///
+/// # Examples
+/// ```
+/// use wuwa_calculator::echo::{max_echo_level, MAX_ECHO_LEVEL};
+///
+/// assert_eq!(max_echo_level(5), Some(MAX_ECHO_LEVEL));
+/// assert_eq!(max_echo_level(4), Some(20));
+/// assert_eq!(max_echo_level(1), None);
+/// ```
+pub fn max_echo_level(rarity: usize) -> Option<isize> {
+    (2..=5).contains(&rarity).then_some(5 * rarity as isize)
+}
+
+/// The `StatType` enum represents the different types of stats that can be added to a character through an echo, weapon or buff
+#[derive(Copy, Clone, PartialEq, Debug)]
 pub enum StatType {
     // Main Stats
     AtkFlat,
@@ -17,6 +43,7 @@ pub enum StatType {
     AeroDmg,
     SpectroDmg,
     HavocDmg,
+    AllElementDmg,
     // Crit, ER
     EnergyRegen,
     CritRate,
@@ -27,78 +54,369 @@ pub enum StatType {
     BasicDmg,
     HeavyDmg,
     LiberationDmg,
+    // Penetration Stats
+    DefIgnore,
+    GlacioResPen,
+    FusionResPen,
+    ElectroResPen,
+    AeroResPen,
+    SpectroResPen,
+    HavocResPen,
+    // Deepen Stats
+    GlacioDeepen,
+    FusionDeepen,
+    ElectroDeepen,
+    AeroDeepen,
+    SpectroDeepen,
+    HavocDeepen,
+    SkillDeepen,
+    BasicDeepen,
+    HeavyDeepen,
+    LiberationDeepen,
+    // Base Stats
+    BaseAtk,
     // Unrolled
     None,
 }
 
 impl StatType {
-    fn add_to_stats(&self, stats: &mut Stats, value: f64) {
+    /// Every stat type, except `StatType::None`, in declaration order
+    pub const ALL: [StatType; 39] = [
+        StatType::AtkFlat, StatType::AtkMult, StatType::HpFlat, StatType::HpMult, StatType::DefFlat,
+        StatType::DefMult, StatType::GlacioDmg, StatType::FusionDmg, StatType::ElectroDmg, StatType::AeroDmg,
+        StatType::SpectroDmg, StatType::HavocDmg, StatType::AllElementDmg, StatType::EnergyRegen,
+        StatType::CritRate, StatType::CritDmg, StatType::Healing, StatType::SkillDmg, StatType::BasicDmg,
+        StatType::HeavyDmg, StatType::LiberationDmg, StatType::DefIgnore, StatType::GlacioResPen,
+        StatType::FusionResPen, StatType::ElectroResPen, StatType::AeroResPen, StatType::SpectroResPen,
+        StatType::HavocResPen, StatType::GlacioDeepen, StatType::FusionDeepen, StatType::ElectroDeepen,
+        StatType::AeroDeepen, StatType::SpectroDeepen, StatType::HavocDeepen, StatType::SkillDeepen,
+        StatType::BasicDeepen, StatType::HeavyDeepen, StatType::LiberationDeepen, StatType::BaseAtk,
+    ];
+
+    /// Checks if the stat is a flat amount, like flat ATK, rather than a percentage
+    pub fn is_flat(&self) -> bool {
+        matches!(self, StatType::AtkFlat | StatType::HpFlat | StatType::DefFlat | StatType::BaseAtk)
+    }
+
+    /// Returns the values the stat can roll as a substat, from the lowest tier to the highest, or `None` if echoes
+    /// can't roll it
+    pub fn sub_stat_tiers(&self) -> Option<&'static [f64]> {
+        SUB_STAT_TIERS.iter()
+            .find(|tiers| tiers.stat_type == *self)
+            .map(|tiers| tiers.values)
+    }
+
+    /// Returns the value of a typical roll of the stat on an echo: the average of its substat tiers, or for stats that
+    /// are only main stats, like elemental DMG bonuses, the largest value they have as a main stat. Returns `None` for
+    /// stats echoes never have
+    pub fn typical_roll(&self) -> Option<f64> {
+        if let Some(values) = self.sub_stat_tiers() {
+            return Some(values.iter().sum::<f64>() / values.len() as f64);
+        }
+        COST_STATS.iter()
+            .flat_map(|cost_stats| cost_stats.main_stats)
+            .filter(|main_stat| main_stat.stat_type == *self)
+            .map(|main_stat| main_stat.max_value)
+            .reduce(f64::max)
+    }
+
+    /// Returns which tier a substat value is and how good of a roll it is, or `None` if it isn't a roll of the stat
+    ///
+    /// # Examples
+    /// ```
+    /// use wuwa_calculator::echo::StatType;
+    ///
+    /// let roll = StatType::CritDmg.sub_stat_roll(0.21).unwrap();
+    /// assert_eq!((roll.tier, roll.tiers, roll.quality), (7, 8, 1.0));
+    /// assert_eq!(StatType::CritDmg.sub_stat_roll(0.2), None);
+    /// ```
+    pub fn sub_stat_roll(&self, value: f64) -> Option<SubStatRoll> {
+        let values = self.sub_stat_tiers()?;
+        let tier = values.iter().position(|tier| (value - tier).abs() <= tier * SUB_STAT_TOLERANCE)?;
+        let (lowest, highest) = (values[0], values[values.len() - 1]);
+        Some(SubStatRoll {
+            tier,
+            tiers: values.len(),
+            quality: (values[tier] - lowest) / (highest - lowest),
+        })
+    }
+
+    pub(crate) fn add_to_stats(&self, stats: &mut Stats, value: f64) {
+        StatModifier::add(*self, value).apply(stats);
+    }
+
+    pub(crate) fn remove_from_stats(&self, stats: &mut Stats, value: f64) {
+        StatModifier::add(*self, value).revert(stats);
+    }
+
+    // The fields of `stats` that hold the stat. Only `AllElementDmg` is held by more than one. Inlined, since the
+    // optimizer applies stats in its innermost loop
+    #[inline]
+    pub(crate) fn fields<'s>(&self, stats: &'s mut Stats) -> &'s mut [f64] {
+        use std::slice::from_mut as one;
         match self {
             // Main Stats
-            StatType::AtkFlat => stats.atk_flat += value,
-            StatType::AtkMult => stats.atk_mult += value,
-            StatType::HpFlat => stats.hp_flat += value,
-            StatType::HpMult => stats.hp_mult += value,
-            StatType::DefFlat => stats.def_flat += value,
-            StatType::DefMult => stats.def_mult += value,
+            StatType::AtkFlat => one(&mut stats.atk_flat),
+            StatType::AtkMult => one(&mut stats.atk_mult),
+            StatType::HpFlat => one(&mut stats.hp_flat),
+            StatType::HpMult => one(&mut stats.hp_mult),
+            StatType::DefFlat => one(&mut stats.def_flat),
+            StatType::DefMult => one(&mut stats.def_mult),
             // Element Stats
-            StatType::GlacioDmg => stats.element_dmg[0] += value,
-            StatType::FusionDmg => stats.element_dmg[1] += value,
-            StatType::ElectroDmg => stats.element_dmg[2] += value,
-            StatType::AeroDmg => stats.element_dmg[3] += value,
-            StatType::SpectroDmg => stats.element_dmg[4] += value,
-            StatType::HavocDmg => stats.element_dmg[5] += value,
+            StatType::GlacioDmg => one(&mut stats.element_dmg[Element::Glacio as usize]),
+            StatType::FusionDmg => one(&mut stats.element_dmg[Element::Fusion as usize]),
+            StatType::ElectroDmg => one(&mut stats.element_dmg[Element::Electro as usize]),
+            StatType::AeroDmg => one(&mut stats.element_dmg[Element::Aero as usize]),
+            StatType::SpectroDmg => one(&mut stats.element_dmg[Element::Spectro as usize]),
+            StatType::HavocDmg => one(&mut stats.element_dmg[Element::Havoc as usize]),
+            StatType::AllElementDmg => &mut stats.element_dmg,
             // Crit, ER
-            StatType::EnergyRegen => stats.energy_regen += value,
-            StatType::CritRate => stats.crit_rate += value,
-            StatType::CritDmg => stats.crit_dmg += value,
-            StatType::Healing => stats.healing_bonus += value,
+            StatType::EnergyRegen => one(&mut stats.energy_regen),
+            StatType::CritRate => one(&mut stats.crit_rate),
+            StatType::CritDmg => one(&mut stats.crit_dmg),
+            StatType::Healing => one(&mut stats.healing_bonus),
             // Skill Stats
-            StatType::SkillDmg => stats.skill_dmg[0] += value,
-            StatType::BasicDmg => stats.skill_dmg[1] += value,
-            StatType::HeavyDmg => stats.skill_dmg[2] += value,
-            StatType::LiberationDmg => stats.skill_dmg[3] += value,
+            StatType::SkillDmg => one(&mut stats.skill_dmg[SkillType::Skill as usize]),
+            StatType::BasicDmg => one(&mut stats.skill_dmg[SkillType::Basic as usize]),
+            StatType::HeavyDmg => one(&mut stats.skill_dmg[SkillType::Heavy as usize]),
+            StatType::LiberationDmg => one(&mut stats.skill_dmg[SkillType::Liberation as usize]),
+            // Penetration Stats
+            StatType::DefIgnore => one(&mut stats.def_ignore),
+            StatType::GlacioResPen => one(&mut stats.res_pen[Element::Glacio as usize]),
+            StatType::FusionResPen => one(&mut stats.res_pen[Element::Fusion as usize]),
+            StatType::ElectroResPen => one(&mut stats.res_pen[Element::Electro as usize]),
+            StatType::AeroResPen => one(&mut stats.res_pen[Element::Aero as usize]),
+            StatType::SpectroResPen => one(&mut stats.res_pen[Element::Spectro as usize]),
+            StatType::HavocResPen => one(&mut stats.res_pen[Element::Havoc as usize]),
+            // Deepen Stats
+            StatType::GlacioDeepen => one(&mut stats.element_deepen[Element::Glacio as usize]),
+            StatType::FusionDeepen => one(&mut stats.element_deepen[Element::Fusion as usize]),
+            StatType::ElectroDeepen => one(&mut stats.element_deepen[Element::Electro as usize]),
+            StatType::AeroDeepen => one(&mut stats.element_deepen[Element::Aero as usize]),
+            StatType::SpectroDeepen => one(&mut stats.element_deepen[Element::Spectro as usize]),
+            StatType::HavocDeepen => one(&mut stats.element_deepen[Element::Havoc as usize]),
+            StatType::SkillDeepen => one(&mut stats.skill_deepen[SkillType::Skill as usize]),
+            StatType::BasicDeepen => one(&mut stats.skill_deepen[SkillType::Basic as usize]),
+            StatType::HeavyDeepen => one(&mut stats.skill_deepen[SkillType::Heavy as usize]),
+            StatType::LiberationDeepen => one(&mut stats.skill_deepen[SkillType::Liberation as usize]),
+            // Base Stats
+            StatType::BaseAtk => one(&mut stats.base_atk),
             // Unrolled
-            StatType::None => (),
+            StatType::None => &mut [],
         }
     }
-    
-    fn remove_from_stats(&self, stats: &mut Stats, value: f64) {
+}
+
+/// The `EchoClass` enum represents the class of the monster an echo was absorbed from
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum EchoClass {
+    Calamity,
+    Overlord,
+    Elite,
+    Common,
+}
+
+impl EchoClass {
+    /// Returns the cost of echoes of the class
+    pub fn cost(&self) -> isize {
         match self {
-            // Main Stats
-            StatType::AtkFlat => stats.atk_flat -= value,
-            StatType::AtkMult => stats.atk_mult -= value,
-            StatType::HpFlat => stats.hp_flat -= value,
-            StatType::HpMult => stats.hp_mult -= value,
-            StatType::DefFlat => stats.def_flat -= value,
-            StatType::DefMult => stats.def_mult -= value,
-            // Element Stats
-            StatType::GlacioDmg => stats.element_dmg[0] -= value,
-            StatType::FusionDmg => stats.element_dmg[1] -= value,
-            StatType::ElectroDmg => stats.element_dmg[2] -= value,
-            StatType::AeroDmg => stats.element_dmg[3] -= value,
-            StatType::SpectroDmg => stats.element_dmg[4] -= value,
-            StatType::HavocDmg => stats.element_dmg[5] -= value,
-            // Crit, ER
-            StatType::EnergyRegen => stats.energy_regen -= value,
-            StatType::CritRate => stats.crit_rate -= value,
-            StatType::CritDmg => stats.crit_dmg -= value,
-            StatType::Healing => stats.healing_bonus -= value,
-            // Skill Stats
-            StatType::SkillDmg => stats.skill_dmg[0] -= value,
-            StatType::BasicDmg => stats.skill_dmg[1] -= value,
-            StatType::HeavyDmg => stats.skill_dmg[2] -= value,
-            StatType::LiberationDmg => stats.skill_dmg[3] -= value,
-            // Unrolled
-            StatType::None => (),
+            EchoClass::Calamity | EchoClass::Overlord => 4,
+            EchoClass::Elite => 3,
+            EchoClass::Common => 1,
         }
     }
 }
 
+/// The `EchoIdentity` struct represents which monster an echo is. The same monster can drop echoes of different
+/// sonata sets, so the set is kept on the `Echo` itself
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct EchoIdentity {
+    /// Id of the monster, such as the one the game's data gives it, so echoes imported at runtime can have one.
+    /// Echoes with the same id only count once towards a set bonus. There's no table of monsters here: the id is
+    /// the one of the data the echoes are imported from, which is where its name is looked up
+    pub id: u32,
+    pub class: EchoClass,
+    /// Damage of the echo's active skill, dealt when it's equipped as the main echo, in the first slot
+    pub skill: Option<Target>,
+}
+
+/// The `MainStat` struct represents a main or secondary stat an echo can have, with its value at the maximum level of
+/// its rarity
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct MainStat {
+    pub stat_type: StatType,
+    pub max_value: f64,
+}
+
+impl MainStat {
+    const fn new(stat_type: StatType, max_value: f64) -> MainStat {
+        MainStat { stat_type, max_value }
+    }
+
+    /// Returns the value of the stat at a level, between 0 and `max_level`, the maximum level of the echo's rarity. It
+    /// grows linearly from a fifth of the maximum value at level 0
+    pub fn value(&self, level: isize, max_level: isize) -> f64 {
+        self.max_value * (0.2 + 0.8 * level as f64 / max_level as f64)
+    }
+}
+
+/// The `CostStats` struct holds the main stats echoes of a rarity and cost can roll, and the secondary stat they all
+/// have
+pub struct CostStats {
+    pub rarity: usize,
+    pub cost: isize,
+    pub main_stats: &'static [MainStat],
+    pub secondary_stat: MainStat,
+}
+
+/// Registry of the main and secondary stats of echoes of each rarity and cost. Only the values of 5-star echoes have
+/// been read off the game so far, so echoes of lower rarities can't be validated until theirs are added
+pub const COST_STATS: [CostStats; 3] = [
+    CostStats {
+        rarity: 5,
+        cost: 4,
+        main_stats: &[
+            MainStat::new(StatType::CritRate, 0.22),
+            MainStat::new(StatType::CritDmg, 0.44),
+            MainStat::new(StatType::HpMult, 0.33),
+            MainStat::new(StatType::AtkMult, 0.33),
+            MainStat::new(StatType::DefMult, 0.418),
+            MainStat::new(StatType::Healing, 0.264),
+        ],
+        secondary_stat: MainStat::new(StatType::AtkFlat, 150.0),
+    },
+    CostStats {
+        rarity: 5,
+        cost: 3,
+        main_stats: &[
+            MainStat::new(StatType::HpMult, 0.30),
+            MainStat::new(StatType::AtkMult, 0.30),
+            MainStat::new(StatType::DefMult, 0.38),
+            MainStat::new(StatType::EnergyRegen, 0.32),
+            MainStat::new(StatType::GlacioDmg, 0.30),
+            MainStat::new(StatType::FusionDmg, 0.30),
+            MainStat::new(StatType::ElectroDmg, 0.30),
+            MainStat::new(StatType::AeroDmg, 0.30),
+            MainStat::new(StatType::SpectroDmg, 0.30),
+            MainStat::new(StatType::HavocDmg, 0.30),
+        ],
+        secondary_stat: MainStat::new(StatType::AtkFlat, 100.0),
+    },
+    CostStats {
+        rarity: 5,
+        cost: 1,
+        main_stats: &[
+            MainStat::new(StatType::HpMult, 0.228),
+            MainStat::new(StatType::AtkMult, 0.18),
+            MainStat::new(StatType::DefMult, 0.18),
+        ],
+        secondary_stat: MainStat::new(StatType::HpFlat, 2280.0),
+    },
+];
+
+// Values read off the game are rounded, so they're accepted this close to the table's, relative to the maximum
+// value. Consecutive levels are 3.2% of the maximum value apart, so this never matches the wrong level
+const MAIN_STAT_TOLERANCE: f64 = 0.01;
+
+const PERCENT_TIERS: [f64; 8] = [0.064, 0.071, 0.079, 0.086, 0.094, 0.101, 0.109, 0.116];
+
+/// The `SubStatTiers` struct holds the values a substat can roll, from the lowest tier to the highest
+pub struct SubStatTiers {
+    pub stat_type: StatType,
+    pub values: &'static [f64],
+}
+
+/// Registry of every stat type echoes can roll as a substat, with their roll tiers
+pub const SUB_STAT_TIERS: [SubStatTiers; 13] = [
+    SubStatTiers { stat_type: StatType::AtkFlat, values: &[30.0, 40.0, 50.0, 60.0] },
+    SubStatTiers { stat_type: StatType::AtkMult, values: &PERCENT_TIERS },
+    SubStatTiers { stat_type: StatType::HpFlat, values: &[320.0, 360.0, 390.0, 430.0, 470.0, 510.0, 540.0, 580.0] },
+    SubStatTiers { stat_type: StatType::HpMult, values: &PERCENT_TIERS },
+    SubStatTiers { stat_type: StatType::DefFlat, values: &[40.0, 50.0, 60.0, 70.0] },
+    SubStatTiers { stat_type: StatType::DefMult, values: &[0.081, 0.090, 0.100, 0.109, 0.118, 0.128, 0.138, 0.147] },
+    SubStatTiers { stat_type: StatType::CritRate, values: &[0.063, 0.069, 0.075, 0.081, 0.087, 0.093, 0.099, 0.105] },
+    SubStatTiers { stat_type: StatType::CritDmg, values: &[0.126, 0.138, 0.150, 0.162, 0.174, 0.186, 0.198, 0.210] },
+    SubStatTiers { stat_type: StatType::EnergyRegen, values: &[0.068, 0.076, 0.084, 0.092, 0.100, 0.108, 0.116, 0.124] },
+    SubStatTiers { stat_type: StatType::BasicDmg, values: &PERCENT_TIERS },
+    SubStatTiers { stat_type: StatType::HeavyDmg, values: &PERCENT_TIERS },
+    SubStatTiers { stat_type: StatType::SkillDmg, values: &PERCENT_TIERS },
+    SubStatTiers { stat_type: StatType::LiberationDmg, values: &PERCENT_TIERS },
+];
+
+// Relative tolerance for substat values, which tiers are always more than 5% apart
+const SUB_STAT_TOLERANCE: f64 = 0.005;
+
+/// The `SubStatRoll` struct represents which tier a substat value rolled
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct SubStatRoll {
+    /// Tier of the roll, with 0 the lowest
+    pub tier: usize,
+    /// Number of tiers the substat has
+    pub tiers: usize,
+    /// How good the roll is, from 0 for the lowest value to 1 for the highest
+    pub quality: f64,
+}
+
+/// The `EchoError` enum represents the reasons an echo can't exist in the game
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum EchoError {
+    /// Echoes only cost 1, 3 or 4
+    InvalidCost(isize),
+    /// Echoes only have 2 to 5 stars
+    InvalidRarity(usize),
+    /// The echo could exist, but the stats of its rarity and cost aren't in `COST_STATS` yet
+    UnknownStats { rarity: usize, cost: isize },
+    /// The level isn't between 0 and the maximum level of the echo's rarity
+    InvalidLevel(isize),
+    /// The echo's cost isn't the cost of its monster's class
+    ClassCostMismatch { class: EchoClass, cost: isize },
+    /// Echoes of the cost can't roll the main stat
+    InvalidMainStat { cost: isize, stat_type: StatType },
+    /// Echoes of the cost have a different secondary stat
+    InvalidSecondaryStat { cost: isize, stat_type: StatType },
+    /// The value isn't one the stat has at any level, or the main and secondary stats are of different levels
+    InvalidStatValue { stat_type: StatType, value: f64 },
+    /// Echoes can't roll the stat as a substat
+    InvalidSubStat(StatType),
+    /// The echo has more than one substat of the type
+    DuplicateSubStat(StatType),
+    /// The value isn't one of the roll tiers of the substat
+    InvalidSubStatValue { stat_type: StatType, value: f64 },
+    /// An unrolled substat, `StatType::None`, has a value
+    UnrolledSubStatValue(f64),
+}
+
+impl fmt::Display for EchoError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EchoError::InvalidCost(cost) => write!(f, "echoes can't cost {}, only 1, 3 or 4", cost),
+            EchoError::InvalidRarity(rarity) => write!(f, "echoes can't have {} stars, only 2 to 5", rarity),
+            EchoError::UnknownStats {rarity, cost} => write!(f, "the stats of {}-star {}-cost echoes aren't known", rarity, cost),
+            EchoError::InvalidLevel(level) => write!(f, "echo level {} is not between 0 and the maximum level of its rarity", level),
+            EchoError::ClassCostMismatch {class, cost} => write!(f, "{:?} echoes cost {}, not {}", class, class.cost(), cost),
+            EchoError::InvalidMainStat {cost, stat_type} => write!(f, "{}-cost echoes can't have {:?} as their main stat", cost, stat_type),
+            EchoError::InvalidSecondaryStat {cost, stat_type} => write!(f, "{}-cost echoes can't have {:?} as their secondary stat", cost, stat_type),
+            EchoError::InvalidStatValue {stat_type, value} => write!(f, "{} is not a value {:?} has at any echo level", value, stat_type),
+            EchoError::InvalidSubStat(stat_type) => write!(f, "echoes can't roll {:?} as a substat", stat_type),
+            EchoError::DuplicateSubStat(stat_type) => write!(f, "echoes can't roll {:?} as a substat more than once", stat_type),
+            EchoError::InvalidSubStatValue {stat_type, value} => write!(f, "{} is not a roll tier of {:?}", value, stat_type),
+            EchoError::UnrolledSubStatValue(value) => write!(f, "an unrolled substat has a value of {}", value),
+        }
+    }
+}
+
+impl Error for EchoError {}
+
 /// The `Echo` struct represents an echo that can be added to a character
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, PartialEq, Debug)]
 pub struct Echo {
     pub cost: isize,
+    /// Number of stars, from 2 to 5
+    pub rarity: usize,
+    pub sonata: Sonata,
+    /// Which monster the echo is, or `None` if it doesn't matter, in which case it's never a duplicate of another
+    /// echo and has no active skill
+    pub identity: Option<EchoIdentity>,
     pub main_stat_type: StatType,
     pub main_stat_value: f64,
     pub secondary_stat_type: StatType,
@@ -107,17 +425,132 @@ pub struct Echo {
 }
 
 impl Echo {
+    /// Creates an echo of a rarity, with the main and secondary stat values it has at `level`. Returns an error if
+    /// echoes of the rarity and cost can't roll the main stat, if the identity's class has a different cost, or if the
+    /// substats aren't valid rolls
+    ///
+    /// # Examples
+    /// ```
+    /// use wuwa_calculator::echo::{Echo, EchoError, StatType};
+    /// use wuwa_calculator::sonata::Sonata;
+    ///
+    /// let echo = Echo::new(4, 5, Sonata::SierraGale, None, StatType::CritRate, 25, [(StatType::None, 0.0); 5]).unwrap();
+    /// assert_eq!(echo.main_stat_value, 0.22);
+    /// assert_eq!(echo.secondary_stat_value, 150.0);
+    ///
+    /// let error = Echo::new(1, 5, Sonata::SierraGale, None, StatType::CritRate, 25, [(StatType::None, 0.0); 5]);
+    /// assert_eq!(error, Err(EchoError::InvalidMainStat {cost: 1, stat_type: StatType::CritRate}));
+    /// ```
+    pub fn new(cost: isize, rarity: usize, sonata: Sonata, identity: Option<EchoIdentity>, main_stat_type: StatType, level: isize, sub_stats: [(StatType, f64); 5]) -> Result<Echo, EchoError> {
+        let (cost_stats, max_level) = cost_stats(rarity, cost, identity)?;
+        if !(0..=max_level).contains(&level) {
+            return Err(EchoError::InvalidLevel(level));
+        }
+        let main_stat = cost_stats.main_stats.iter()
+            .find(|main_stat| main_stat.stat_type == main_stat_type)
+            .ok_or(EchoError::InvalidMainStat {cost, stat_type: main_stat_type})?;
+        validate_sub_stats(&sub_stats)?;
+        Ok(Echo {
+            cost,
+            rarity,
+            sonata,
+            identity,
+            main_stat_type,
+            main_stat_value: main_stat.value(level, max_level),
+            secondary_stat_type: cost_stats.secondary_stat.stat_type,
+            secondary_stat_value: cost_stats.secondary_stat.value(level, max_level),
+            sub_stats,
+        })
+    }
+
+    /// Checks that the echo could exist in the game: that its cost, main stat and secondary stat are ones echoes of its
+    /// rarity have, that their values are both of the same level, and that every substat is a different stat rolled
+    /// at one of its tiers. Values are allowed to be rounded, like the ones the game shows
+    ///
+    /// # Examples
+    /// ```
+    /// use wuwa_calculator::echo::{Echo, EchoError, StatType};
+    /// use wuwa_calculator::sonata::Sonata;
+    ///
+    /// let mut echo = Echo::new(3, 5, Sonata::MoltenRift, None, StatType::FusionDmg, 10, [(StatType::None, 0.0); 5]).unwrap();
+    /// assert_eq!(echo.validate(), Ok(()));
+    ///
+    /// echo.secondary_stat_type = StatType::HpFlat;
+    /// assert_eq!(echo.validate(), Err(EchoError::InvalidSecondaryStat {cost: 3, stat_type: StatType::HpFlat}));
+    /// ```
+    pub fn validate(&self) -> Result<(), EchoError> {
+        self.level().map(|_| ())
+    }
+
+    /// Returns the level of the echo, read off the values of its main and secondary stats, after checking it's
+    /// valid like `Echo::validate` does
+    pub fn level(&self) -> Result<isize, EchoError> {
+        let (cost_stats, max_level) = cost_stats(self.rarity, self.cost, self.identity)?;
+        let main_stat = cost_stats.main_stats.iter()
+            .find(|main_stat| main_stat.stat_type == self.main_stat_type)
+            .ok_or(EchoError::InvalidMainStat {cost: self.cost, stat_type: self.main_stat_type})?;
+        let secondary_stat = cost_stats.secondary_stat;
+        if self.secondary_stat_type != secondary_stat.stat_type {
+            return Err(EchoError::InvalidSecondaryStat {cost: self.cost, stat_type: self.secondary_stat_type});
+        }
+
+        // The level is the closest one to the main stat's value, and the secondary stat has to be of the same one
+        let level = ((self.main_stat_value / main_stat.max_value - 0.2) / 0.8 * max_level as f64).round() as isize;
+        let level = level.clamp(0, max_level);
+        for (stat, value) in [(main_stat, self.main_stat_value), (&secondary_stat, self.secondary_stat_value)] {
+            // NaN would otherwise pass, since it's never farther than the tolerance
+            if !value.is_finite() || (value - stat.value(level, max_level)).abs() > stat.max_value * MAIN_STAT_TOLERANCE {
+                return Err(EchoError::InvalidStatValue {stat_type: stat.stat_type, value});
+            }
+        }
+        validate_sub_stats(&self.sub_stats)?;
+        Ok(level)
+    }
+
+    /// Returns the average quality of the rolled substats of the echo, as described in `SubStatRoll`, or `None` if
+    /// it has none or any of them isn't a valid roll
+    ///
+    /// # Examples
+    /// ```
+    /// use wuwa_calculator::echo::{Echo, StatType};
+    /// use wuwa_calculator::sonata::Sonata;
+    ///
+    /// let sub_stats = [
+    ///     (StatType::CritRate, 0.105),
+    ///     (StatType::CritDmg, 0.126),
+    ///     (StatType::None, 0.0),
+    ///     (StatType::None, 0.0),
+    ///     (StatType::None, 0.0),
+    /// ];
+    /// let echo = Echo::new(4, 5, Sonata::SierraGale, None, StatType::CritRate, 10, sub_stats).unwrap();
+    /// assert_eq!(echo.roll_quality(), Some(0.5));
+    /// ```
+    pub fn roll_quality(&self) -> Option<f64> {
+        let rolls = self.sub_stats.iter()
+            .filter(|(stat_type, _)| *stat_type != StatType::None)
+            .map(|(stat_type, value)| stat_type.sub_stat_roll(*value))
+            .collect::<Option<Vec<SubStatRoll>>>()?;
+        if rolls.is_empty() {
+            return None;
+        }
+        Some(rolls.iter().map(|roll| roll.quality).sum::<f64>() / rolls.len() as f64)
+    }
+
     /// Adds the stats of the echo to the stats of a character
     /// 
     /// # Examples
     /// ```
     /// use wuwa_calculator::calculator::{BaseStats, Stats};
     /// use wuwa_calculator::echo::{Echo, StatType};
+    /// use wuwa_calculator::sonata::Sonata;
     ///
     /// let mut stats = Stats::new_from_base(BaseStats {hp: 7954.0, atk: 343.0, def: 899.0});
     /// 
     /// let echo = Echo {
     ///   cost: 4,
+    ///   rarity: 5,
+    ///   sonata: Sonata::SierraGale,
+    ///   identity: None,
     ///   main_stat_type: StatType::CritRate,
     ///   main_stat_value: 0.22,
     ///   secondary_stat_type: StatType::AtkFlat,
@@ -136,10 +569,8 @@ impl Echo {
     /// ```
     /// 
     pub fn add_to_stats(&self, stats: &mut Stats) {
-        self.main_stat_type.add_to_stats(stats, self.main_stat_value);
-        self.secondary_stat_type.add_to_stats(stats, self.secondary_stat_value);
-        for (stat_type, value) in &self.sub_stats {
-            stat_type.add_to_stats(stats, *value);
+        for modifier in self.modifiers() {
+            modifier.apply(stats);
         }
     }
 
@@ -149,11 +580,15 @@ impl Echo {
     /// ```
     /// use wuwa_calculator::calculator::{BaseStats, Stats};
     /// use wuwa_calculator::echo::{Echo, StatType};
+    /// use wuwa_calculator::sonata::Sonata;
     ///
     /// let mut stats = Stats::new_from_base(BaseStats {hp: 7954.0, atk: 343.0, def: 899.0});
     ///
     /// let echo = Echo {
     ///   cost: 4,
+    ///   rarity: 5,
+    ///   sonata: Sonata::SierraGale,
+    ///   identity: None,
     ///   main_stat_type: StatType::CritRate,
     ///   main_stat_value: 0.22,
     ///   secondary_stat_type: StatType::AtkFlat,
@@ -173,10 +608,63 @@ impl Echo {
     /// ```
     ///
     pub fn remove_from_stats(&self, stats: &mut Stats) {
-        self.main_stat_type.remove_from_stats(stats, self.main_stat_value);
-        self.secondary_stat_type.remove_from_stats(stats, self.secondary_stat_value);
-        for (stat_type, value) in &self.sub_stats {
-            stat_type.remove_from_stats(stats, *value);
+        for modifier in self.modifiers() {
+            modifier.revert(stats);
+        }
+    }
+
+    /// Returns the modifiers the echo applies to a character: its main stat, secondary stat and substats
+    pub fn modifiers(&self) -> impl Iterator<Item = StatModifier> {
+        [(self.main_stat_type, self.main_stat_value), (self.secondary_stat_type, self.secondary_stat_value)]
+            .into_iter()
+            .chain(self.sub_stats)
+            .map(|(stat_type, value)| StatModifier::add(stat_type, value))
+    }
+
+    /// Checks if two echoes are copies of the same monster
+    pub fn same_monster(&self, other: &Echo) -> bool {
+        matches!((self.identity, other.identity), (Some(a), Some(b)) if a.id == b.id)
+    }
+
+    /// Returns the active skill of the echo, if it has one
+    pub fn skill(&self) -> Option<Target> {
+        self.identity.and_then(|identity| identity.skill)
+    }
+}
+
+// Returns the main and secondary stats of echoes of a rarity and cost, and the maximum level of the rarity, checking
+// the cost matches the class of the echo's monster
+fn cost_stats(rarity: usize, cost: isize, identity: Option<EchoIdentity>) -> Result<(&'static CostStats, isize), EchoError> {
+    let max_level = max_echo_level(rarity).ok_or(EchoError::InvalidRarity(rarity))?;
+    if !COST_STATS.iter().any(|cost_stats| cost_stats.cost == cost) {
+        return Err(EchoError::InvalidCost(cost));
+    }
+    if let Some(identity) = identity.filter(|identity| identity.class.cost() != cost) {
+        return Err(EchoError::ClassCostMismatch {class: identity.class, cost});
+    }
+    COST_STATS.iter()
+        .find(|cost_stats| cost_stats.rarity == rarity && cost_stats.cost == cost)
+        .map(|cost_stats| (cost_stats, max_level))
+        .ok_or(EchoError::UnknownStats {rarity, cost})
+}
+
+fn validate_sub_stats(sub_stats: &[(StatType, f64); 5]) -> Result<(), EchoError> {
+    for (i, &(stat_type, value)) in sub_stats.iter().enumerate() {
+        if stat_type == StatType::None {
+            if value != 0.0 {
+                return Err(EchoError::UnrolledSubStatValue(value));
+            }
+            continue;
+        }
+        if stat_type.sub_stat_tiers().is_none() {
+            return Err(EchoError::InvalidSubStat(stat_type));
+        }
+        if sub_stats[..i].iter().any(|(other, _)| *other == stat_type) {
+            return Err(EchoError::DuplicateSubStat(stat_type));
+        }
+        if stat_type.sub_stat_roll(value).is_none() {
+            return Err(EchoError::InvalidSubStatValue {stat_type, value});
         }
     }
+    Ok(())
 }
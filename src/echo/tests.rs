@@ -0,0 +1,89 @@
+use crate::echo::{max_echo_level, Echo, EchoClass, EchoError, EchoIdentity, StatType, COST_STATS, SUB_STAT_TIERS};
+use crate::sonata::Sonata;
+
+#[test]
+fn test_echo_main_stats_by_cost() {
+    let no_sub_stats = [(StatType::None, 0.0); 5];
+    for cost_stats in &COST_STATS {
+        for main_stat in cost_stats.main_stats {
+            for level in 0..=max_echo_level(cost_stats.rarity).unwrap() {
+                let echo = Echo::new(cost_stats.cost, cost_stats.rarity, Sonata::None, None, main_stat.stat_type, level, no_sub_stats).unwrap();
+                assert_eq!(echo.validate(), Ok(()));
+            }
+        }
+    }
+
+    // Values from the game, rounded to what it shows
+    let echo = Echo::new(4, 5, Sonata::None, None, StatType::CritDmg, 0, no_sub_stats).unwrap();
+    assert!((echo.main_stat_value - 0.088).abs() < 1e-12);
+    assert!((echo.secondary_stat_value - 30.0).abs() < 1e-12);
+    let rounded = Echo { main_stat_value: 0.228, secondary_stat_value: 77.0, ..Echo::new(4, 5, Sonata::None, None, StatType::CritDmg, 5, no_sub_stats).unwrap() };
+    assert_eq!(rounded.validate(), Ok(()));
+
+    assert_eq!(Echo::new(2, 5, Sonata::None, None, StatType::AtkMult, 25, no_sub_stats), Err(EchoError::InvalidCost(2)));
+    assert_eq!(Echo::new(3, 5, Sonata::None, None, StatType::AtkMult, 26, no_sub_stats), Err(EchoError::InvalidLevel(26)));
+    assert_eq!(Echo::new(3, 6, Sonata::None, None, StatType::AtkMult, 25, no_sub_stats), Err(EchoError::InvalidRarity(6)));
+    // Lower rarities exist, but their values aren't in the table, so they're reported as unknown rather than invalid
+    assert_eq!(Echo::new(4, 4, Sonata::None, None, StatType::CritRate, 20, no_sub_stats), Err(EchoError::UnknownStats {rarity: 4, cost: 4}));
+    let four_star = Echo { rarity: 4, ..Echo::new(4, 5, Sonata::None, None, StatType::CritRate, 20, no_sub_stats).unwrap() };
+    assert_eq!(four_star.validate(), Err(EchoError::UnknownStats {rarity: 4, cost: 4}));
+    assert_eq!(Echo::new(1, 5, Sonata::None, None, StatType::CritRate, 25, no_sub_stats),
+               Err(EchoError::InvalidMainStat {cost: 1, stat_type: StatType::CritRate}));
+    let identity = EchoIdentity {id: 1, class: EchoClass::Overlord, skill: None};
+    assert_eq!(Echo::new(3, 5, Sonata::None, Some(identity), StatType::AeroDmg, 25, no_sub_stats),
+               Err(EchoError::ClassCostMismatch {class: EchoClass::Overlord, cost: 3}));
+
+    let echo = Echo::new(4, 5, Sonata::None, None, StatType::AtkMult, 25, no_sub_stats).unwrap();
+    let secondary = Echo { secondary_stat_type: StatType::HpFlat, secondary_stat_value: 2280.0, ..echo };
+    assert_eq!(secondary.validate(), Err(EchoError::InvalidSecondaryStat {cost: 4, stat_type: StatType::HpFlat}));
+    let main_value = Echo { main_stat_value: 0.155, ..echo };
+    assert_eq!(main_value.validate(), Err(EchoError::InvalidStatValue {stat_type: StatType::AtkMult, value: 0.155}));
+    // Both stats are valid on their own, but not at the same level
+    let mismatched = Echo { secondary_stat_value: 100.0, ..echo };
+    assert_eq!(mismatched.validate(), Err(EchoError::InvalidStatValue {stat_type: StatType::AtkFlat, value: 100.0}));
+    for value in [f64::NAN, f64::INFINITY] {
+        let main_value = Echo { main_stat_value: value, ..echo };
+        assert!(matches!(main_value.validate(), Err(EchoError::InvalidStatValue {stat_type: StatType::AtkMult, ..})));
+        let secondary_value = Echo { secondary_stat_value: value, ..echo };
+        assert!(matches!(secondary_value.validate(), Err(EchoError::InvalidStatValue {stat_type: StatType::AtkFlat, ..})));
+        let sub_stat_value = Echo { sub_stats: [(StatType::CritRate, value), (StatType::None, 0.0), (StatType::None, 0.0), (StatType::None, 0.0), (StatType::None, 0.0)], ..echo };
+        assert!(matches!(sub_stat_value.validate(), Err(EchoError::InvalidSubStatValue {stat_type: StatType::CritRate, ..})));
+    }
+}
+
+#[test]
+fn test_echo_sub_stats() {
+    for tiers in &SUB_STAT_TIERS {
+        for (tier, value) in tiers.values.iter().enumerate() {
+            let roll = tiers.stat_type.sub_stat_roll(*value).unwrap();
+            assert_eq!((roll.tier, roll.tiers), (tier, tiers.values.len()));
+        }
+        assert_eq!(tiers.stat_type.sub_stat_roll(tiers.values[0]).unwrap().quality, 0.0);
+        assert_eq!(tiers.stat_type.sub_stat_roll(tiers.values[tiers.values.len() - 1]).unwrap().quality, 1.0);
+    }
+    // Percentages converted from what the game shows still match their tier
+    assert_eq!(StatType::HpMult.sub_stat_roll(8.6 / 100.0).unwrap().tier, 3);
+    assert_eq!(StatType::AtkFlat.sub_stat_roll(45.0), None);
+    assert_eq!(StatType::AeroDmg.sub_stat_roll(0.1), None);
+
+    let echo = Echo::new(1, 5, Sonata::None, None, StatType::AtkMult, 25, [
+        (StatType::CritRate, 0.081),
+        (StatType::CritDmg, 0.174),
+        (StatType::AtkMult, 0.109),
+        (StatType::None, 0.0),
+        (StatType::None, 0.0),
+    ]).unwrap();
+    assert_eq!(echo.validate(), Ok(()));
+    assert!((echo.roll_quality().unwrap() - (3.0 / 7.0 + 4.0 / 7.0 + 0.045 / 0.052) / 3.0).abs() < 1e-12);
+
+    let with_sub_stat = |i: usize, sub_stat: (StatType, f64)| {
+        let mut echo = echo;
+        echo.sub_stats[i] = sub_stat;
+        echo.validate()
+    };
+    assert_eq!(with_sub_stat(1, (StatType::CritRate, 0.063)), Err(EchoError::DuplicateSubStat(StatType::CritRate)));
+    assert_eq!(with_sub_stat(3, (StatType::None, 0.05)), Err(EchoError::UnrolledSubStatValue(0.05)));
+    assert_eq!(with_sub_stat(3, (StatType::HavocDmg, 0.094)), Err(EchoError::InvalidSubStat(StatType::HavocDmg)));
+    assert_eq!(with_sub_stat(3, (StatType::EnergyRegen, 0.08)), Err(EchoError::InvalidSubStatValue {stat_type: StatType::EnergyRegen, value: 0.08}));
+    assert_eq!(Echo { sub_stats: [(StatType::None, 0.0); 5], ..echo }.roll_quality(), None);
+}
@@ -1,9 +1,15 @@
 
 pub mod calculator;
+pub mod character;
 pub mod echo;
+pub mod modifier;
 pub mod optimizer;
+pub mod scoring;
+pub mod simulation;
+pub mod sonata;
+pub mod weapon;
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, PartialEq, Debug)]
 pub enum Element {
     Glacio,
     Fusion,
@@ -13,10 +19,24 @@ pub enum Element {
     Havoc,
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, PartialEq, Debug)]
 pub enum SkillType {
     Skill = 0,
     Basic,
     Heavy,
     Liberation,
-}
\ No newline at end of file
+}
+
+/// Finds the two level breakpoints of the same ascension that surround `level`, along with how far `level` is
+/// between them. Returns `None` if `level` is outside the breakpoints of that ascension
+pub(crate) fn level_breakpoints<T>(points: &[T], level: isize, ascension: usize, key: impl Fn(&T) -> (isize, usize)) -> Option<(&T, &T, f64)> {
+    let points = points.iter().filter(|point| key(point).1 == ascension);
+    let lower = points.clone().filter(|point| key(point).0 <= level).max_by_key(|point| key(point).0)?;
+    let upper = points.filter(|point| key(point).0 >= level).min_by_key(|point| key(point).0)?;
+
+    let (lower_level, upper_level) = (key(lower).0, key(upper).0);
+    if lower_level == upper_level {
+        return Some((lower, upper, 0.0));
+    }
+    Some((lower, upper, (level - lower_level) as f64 / (upper_level - lower_level) as f64))
+}
@@ -1,14 +1,25 @@
-use wuwa_calculator::{Element, SkillType};
-use wuwa_calculator::calculator::{BaseStats, Stats, Target};
+use wuwa_calculator::calculator::Enemy;
+use wuwa_calculator::character::{Character, JIYAN};
 use wuwa_calculator::echo::{Echo, StatType};
-use wuwa_calculator::optimizer::optimize;
+use wuwa_calculator::sonata::{add_set_bonuses, Sonata};
+use wuwa_calculator::weapon::{Weapon, HELIOS_CLEAVER, VERDANT_SUMMIT};
+use wuwa_calculator::optimizer::{optimize_weapons, OptimizeOptions};
 
 fn main() {
-    let mut stats = Stats::new_from_base(BaseStats {hp: 7954.0, atk: 343.0, def: 899.0});
+    let jiyan = Character {
+        data: &JIYAN,
+        level: 70,
+        ascension: 4,
+        minor_forte: [true, true, true, true, false, false, false, false],
+    };
+    let mut stats = jiyan.stats().unwrap();
     
     let echoes = [
         Echo { // Bad artifact, should not be picked
             cost: 4,
+            rarity: 5,
+            sonata: Sonata::None,
+            identity: None,
             main_stat_type: StatType::AtkMult,
             main_stat_value: 0.151,
             secondary_stat_type: StatType::AtkFlat,
@@ -23,6 +34,9 @@ fn main() {
         },
         Echo {
             cost: 4,
+            rarity: 5,
+            sonata: Sonata::SierraGale,
+            identity: None,
             main_stat_type: StatType::CritRate,
             main_stat_value: 0.22,
             secondary_stat_type: StatType::AtkFlat,
@@ -37,6 +51,9 @@ fn main() {
         },
         Echo {
             cost: 3,
+            rarity: 5,
+            sonata: Sonata::LingeringTunes,
+            identity: None,
             main_stat_type: StatType::EnergyRegen,
             main_stat_value: 0.268,
             secondary_stat_type: StatType::AtkFlat,
@@ -51,6 +68,9 @@ fn main() {
         },
         Echo {
             cost: 3,
+            rarity: 5,
+            sonata: Sonata::SierraGale,
+            identity: None,
             main_stat_type: StatType::AeroDmg,
             main_stat_value: 0.30,
             secondary_stat_type: StatType::AtkFlat,
@@ -65,6 +85,9 @@ fn main() {
         },
         Echo { // Bad artifact, should not be picked
             cost: 1,
+            rarity: 5,
+            sonata: Sonata::None,
+            identity: None,
             main_stat_type: StatType::AtkMult,
             main_stat_value: 0.122,
             secondary_stat_type: StatType::HpFlat,
@@ -79,6 +102,9 @@ fn main() {
         },
         Echo { // Bad artifact, should not be picked
             cost: 1,
+            rarity: 5,
+            sonata: Sonata::None,
+            identity: None,
             main_stat_type: StatType::AtkMult,
             main_stat_value: 0.122,
             secondary_stat_type: StatType::HpFlat,
@@ -93,6 +119,9 @@ fn main() {
         },
         Echo { // Bad artifact, should not be picked
             cost: 1,
+            rarity: 5,
+            sonata: Sonata::None,
+            identity: None,
             main_stat_type: StatType::AtkMult,
             main_stat_value: 0.122,
             secondary_stat_type: StatType::HpFlat,
@@ -107,6 +136,9 @@ fn main() {
         },
         Echo { // Bad artifact, should not be picked
             cost: 1,
+            rarity: 5,
+            sonata: Sonata::None,
+            identity: None,
             main_stat_type: StatType::AtkMult,
             main_stat_value: 0.122,
             secondary_stat_type: StatType::HpFlat,
@@ -121,6 +153,9 @@ fn main() {
         },
         Echo { // Bad artifact, should not be picked
             cost: 1,
+            rarity: 5,
+            sonata: Sonata::None,
+            identity: None,
             main_stat_type: StatType::AtkMult,
             main_stat_value: 0.122,
             secondary_stat_type: StatType::HpFlat,
@@ -135,6 +170,9 @@ fn main() {
         },
         Echo {
             cost: 1,
+            rarity: 5,
+            sonata: Sonata::LingeringTunes,
+            identity: None,
             main_stat_type: StatType::AtkMult,
             main_stat_value: 0.151,
             secondary_stat_type: StatType::HpFlat,
@@ -149,6 +187,9 @@ fn main() {
         },
         Echo {
             cost: 1,
+            rarity: 5,
+            sonata: Sonata::None,
+            identity: None,
             main_stat_type: StatType::AtkMult,
             main_stat_value: 0.122,
             secondary_stat_type: StatType::HpFlat,
@@ -163,41 +204,33 @@ fn main() {
         },
     ];
 
-    let optimization_target = Target {
-        element: Element::Aero,
-        skill_type: SkillType::Basic,
-        skill_multiplier: 0.5007,
-        skill_scaling_bonus: 1.0,
-    };
+    let optimization_target = jiyan.skill("Basic Attack Stage 1", 5).unwrap();
     
-    let echoes = optimize(stats.clone(), &echoes, optimization_target, 70, 70);
-    echoes[0].add_to_stats(&mut stats);
-    echoes[1].add_to_stats(&mut stats);
-    echoes[2].add_to_stats(&mut stats);
-    echoes[3].add_to_stats(&mut stats);
-    echoes[4].add_to_stats(&mut stats);
+    let weapons = [
+        Weapon::new(&VERDANT_SUMMIT, 60, 3, 1).unwrap(),
+        Weapon::new(&HELIOS_CLEAVER, 70, 4, 1).unwrap(),
+    ];
 
-    // TODO add echo sets functionality. Hardcoding for now. 
-    // Set bonus
-    stats.atk_mult += 0.10;
-    stats.element_dmg[Element::Aero as usize] += 0.10;
-    
-    // TODO add weapon functionality. Hardcoding for now
-    // Weapon
-    stats.base_atk += 374.0;
-    stats.crit_dmg += 0.359;
-    for i in 0..6 { // weapon passive
-        stats.element_dmg[i] += 0.12;
+    let (weapon, echoes) = match optimize_weapons(stats.clone(), &weapons, &echoes, optimization_target, 70, Enemy::new(70), &OptimizeOptions::default()) {
+        Ok(result) => result,
+        Err(error) => {
+            eprintln!("Optimization failed: {}", error);
+            return;
+        }
+    };
+    for echo in &echoes {
+        echo.add_to_stats(&mut stats);
     }
+
+    add_set_bonuses(&echoes, &mut stats, true);
     
-    // Forte Stats
-    stats.crit_rate += 0.012 + 0.012;
-    stats.atk_mult += 0.018 + 0.018;
-    stats.crit_dmg += 0.12;
+    weapon.add_to_stats(&mut stats, true);
 
+    println!("Weapon: {}", weapon.data.name);
     println!("HP: {}", stats.hp());
     println!("ATK: {}", stats.atk());
     println!("DEF: {}", stats.def());
+    println!("Effective HP: {}", stats.effective_hp(Enemy::new(70)));
     println!("Crit Rate: {}", stats.crit_rate);
     println!("Crit Damage: {}", stats.crit_dmg);
     println!("Energy Regen: {}", stats.energy_regen);
@@ -211,8 +244,8 @@ fn main() {
     println!("No defense average:     {}", stats.skill_base_damage_average(optimization_target));
     println!();
     for i in 62..=70 {
-        println!("Lvl {} non-crit: {}", i, stats.skill_adjusted_damage_noncrit(optimization_target, 70, i));
-        println!("Lvl {} crit:     {}", i, stats.skill_adjusted_damage_crit(optimization_target, 70, i));
+        println!("Lvl {} non-crit: {}", i, stats.skill_adjusted_damage_noncrit(optimization_target, 70, Enemy::new(i)));
+        println!("Lvl {} crit:     {}", i, stats.skill_adjusted_damage_crit(optimization_target, 70, Enemy::new(i)));
         println!();
     }
 }
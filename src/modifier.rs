@@ -0,0 +1,75 @@
+use crate::calculator::Stats;
+use crate::echo::StatType;
+
+#[cfg(test)]
+mod tests;
+
+/// The `Operation` enum represents how a `StatModifier` changes its stat
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Operation {
+    /// Adds a value to the stat
+    Add(f64),
+    /// Multiplies the stat by a factor. A factor of 0 can't be reverted
+    Multiply(f64),
+}
+
+/// The `StatModifier` struct represents a change to one stat of a character. Echoes, weapons, set bonuses and
+/// buffs all change stats through modifiers, and every modifier can be reverted to take its change back
+///
+/// # Examples
+/// ```
+/// use wuwa_calculator::calculator::{BaseStats, Stats};
+/// use wuwa_calculator::echo::StatType;
+/// use wuwa_calculator::modifier::StatModifier;
+///
+/// let mut stats = Stats::new_from_base(BaseStats {hp: 7954.0, atk: 343.0, def: 899.0});
+/// let buffs = [StatModifier::add(StatType::AllElementDmg, 0.2), StatModifier::multiply(StatType::AeroDmg, 2.0)];
+/// for buff in &buffs {
+///     buff.apply(&mut stats);
+/// }
+/// assert_eq!(stats.element_dmg, [0.2, 0.2, 0.2, 0.4, 0.2, 0.2]);
+///
+/// for buff in buffs.iter().rev() {
+///     buff.revert(&mut stats);
+/// }
+/// assert_eq!(stats.element_dmg, [0.0; 6]);
+/// ```
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct StatModifier {
+    pub stat_type: StatType,
+    pub operation: Operation,
+}
+
+impl StatModifier {
+    /// Creates a modifier that adds `value` to a stat
+    pub const fn add(stat_type: StatType, value: f64) -> StatModifier {
+        StatModifier { stat_type, operation: Operation::Add(value) }
+    }
+
+    /// Creates a modifier that multiplies a stat by `factor`
+    pub const fn multiply(stat_type: StatType, factor: f64) -> StatModifier {
+        StatModifier { stat_type, operation: Operation::Multiply(factor) }
+    }
+
+    /// Applies the change to the stats of a character
+    #[inline]
+    pub fn apply(&self, stats: &mut Stats) {
+        for field in self.stat_type.fields(stats) {
+            match self.operation {
+                Operation::Add(value) => *field += value,
+                Operation::Multiply(factor) => *field *= factor,
+            }
+        }
+    }
+
+    /// Takes back the change `apply` made to the stats of a character. Modifiers that were applied after this one
+    /// should be reverted first, since multiplying doesn't commute with adding
+    pub fn revert(&self, stats: &mut Stats) {
+        for field in self.stat_type.fields(stats) {
+            match self.operation {
+                Operation::Add(value) => *field -= value,
+                Operation::Multiply(factor) => *field /= factor,
+            }
+        }
+    }
+}
@@ -0,0 +1,61 @@
+use crate::calculator::{BaseStats, Stats};
+use crate::echo::{Echo, StatType};
+use crate::modifier::StatModifier;
+use crate::sonata::{add_set_bonuses, set_bonus_modifiers, Sonata};
+use crate::weapon::{Weapon, VERDANT_SUMMIT};
+
+#[test]
+fn test_stat_modifiers() {
+    let mut base = Stats::new_from_base(BaseStats {hp: 10000.0, atk: 400.0, def: 1000.0});
+    // Powers of two, so reverting is exact
+    base.crit_rate = 0.25;
+
+    // Every stat changes its own field, and reverting takes the change back
+    for (i, stat_type) in StatType::ALL.iter().enumerate() {
+        let mut stats = base.clone();
+        let modifiers = [StatModifier::add(*stat_type, 0.5), StatModifier::multiply(*stat_type, 4.0)];
+        for modifier in &modifiers {
+            modifier.apply(&mut stats);
+        }
+        assert_ne!(stats, base);
+        for other in &StatType::ALL[..i] {
+            let mut other_stats = base.clone();
+            StatModifier::add(*other, 0.5).apply(&mut other_stats);
+            if *stat_type != StatType::AllElementDmg && *other != StatType::AllElementDmg {
+                assert_ne!(stats, other_stats);
+            }
+        }
+        for modifier in modifiers.iter().rev() {
+            modifier.revert(&mut stats);
+        }
+        assert_eq!(stats, base);
+    }
+    let mut stats = base.clone();
+    StatModifier::add(StatType::None, 1.0).apply(&mut stats);
+    assert_eq!(stats, base);
+
+    // Echoes, weapons and set bonuses apply their stats through modifiers
+    let mut sub_stats = [(StatType::None, 0.0); 5];
+    sub_stats[0] = (StatType::CritRate, 0.081);
+    let echo = Echo::new(1, 5, Sonata::LingeringTunes, None, StatType::AtkMult, 25, sub_stats).unwrap();
+    let weapon = Weapon::new(&VERDANT_SUMMIT, 60, 3, 1).unwrap();
+    let mut expected = base.clone();
+    echo.add_to_stats(&mut expected);
+    weapon.add_to_stats(&mut expected, true);
+    add_set_bonuses(&[echo, echo], &mut expected, true);
+
+    let mut stats = base.clone();
+    let modifiers: Vec<StatModifier> = echo.modifiers()
+        .chain(weapon.modifiers(true))
+        .chain(set_bonus_modifiers(&[echo, echo], true))
+        .collect();
+    for modifier in &modifiers {
+        modifier.apply(&mut stats);
+    }
+    assert_eq!(stats, expected);
+    for modifier in modifiers.iter().rev() {
+        modifier.revert(&mut stats);
+    }
+    assert_eq!(stats, base);
+    assert_eq!(weapon.modifiers(false).count(), VERDANT_SUMMIT.passive.iter().filter(|effect| !effect.conditional).count() + 2);
+}
@@ -1,35 +1,530 @@
-use itertools::Itertools;
-use crate::calculator::{Stats, Target};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::sync::Arc;
+use std::thread;
+use crate::calculator::{Enemy, HealTarget, Stats, Target};
 use crate::echo::Echo;
+use crate::optimizer::search::{Candidate, Search, SkillScore, SkillScoreFn};
+use crate::sonata::add_set_bonuses;
+use crate::weapon::Weapon;
 
+mod search;
+#[cfg(test)]
+mod tests;
 
-// Temporary, rudimentary optimization function. Must receive at least 5 echoes. The number of combinations with this approach blows up quickly.
-pub fn optimize(mut stats: Stats, echoes: &[Echo], target: Target, character_level: isize, enemy_level: isize) -> [Echo; 5] {
-    let best_combo = echoes.iter()
-        .tuple_combinations()
-        .filter(|comb: &(&Echo, &Echo, &Echo, &Echo, &Echo)| {
-            comb.0.cost + comb.1.cost + comb.2.cost + comb.3.cost + comb.4.cost <= 12
-        })
-        .map(|comb| {
-            // This is ugly, but you can't iterate over a tuple
-            // .combinations instead of tuple_combinations would clean this up,
-            // but it creates a Vec to store each combination, which would be significantly less efficient
-            comb.0.add_to_stats(&mut stats);
-            comb.1.add_to_stats(&mut stats);
-            comb.2.add_to_stats(&mut stats);
-            comb.3.add_to_stats(&mut stats);
-            comb.4.add_to_stats(&mut stats);
-            
-            let damage = stats.skill_adjusted_damage_noncrit(target, character_level, enemy_level);
-            
-            comb.0.remove_from_stats(&mut stats);
-            comb.1.remove_from_stats(&mut stats);
-            comb.2.remove_from_stats(&mut stats);
-            comb.3.remove_from_stats(&mut stats);
-            comb.4.remove_from_stats(&mut stats);
-            (damage, comb)
+/// Maximum number of echoes a character can equip
+pub const MAX_ECHOES: usize = 5;
+/// Maximum total cost of the echoes a character can equip
+pub const MAX_COST: isize = 12;
+
+// Number of builds a team member tries when trading echoes with another member
+const TEAM_TRADE_BUILDS: usize = 8;
+// Number of rounds of trades the team optimizer makes at most. Every round makes the team better, so this only
+// bounds how long it takes on large inventories
+const TEAM_MAX_ROUNDS: usize = 16;
+
+/// The `OptimizeError` enum represents the reasons the optimizer can fail to find a build
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum OptimizeError {
+    /// There were no echoes to build from
+    NotEnoughEchoes,
+    /// There were no weapons to pick from
+    NotEnoughWeapons,
+    /// None of the echoes fit within the cost limit
+    NoValidCombination,
+    /// No combination of echoes meets the constraints
+    ConstraintsNotMet,
+    /// The locked or excluded echoes aren't in the inventory, or the locked ones can't be equipped together
+    InvalidLocks,
+    /// A build's damage was NaN or infinite, so builds couldn't be compared
+    NonFiniteDamage,
+    /// A weight of a rotation or of a team member, or the number of echo skill uses, was negative or not a finite number
+    InvalidWeight,
+}
+
+impl fmt::Display for OptimizeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OptimizeError::NotEnoughEchoes => write!(f, "no echoes to build from"),
+            OptimizeError::NotEnoughWeapons => write!(f, "no weapons to pick from"),
+            OptimizeError::NoValidCombination => write!(f, "no combination of echoes fits within the cost limit of {}", MAX_COST),
+            OptimizeError::ConstraintsNotMet => write!(f, "no combination of echoes meets the constraints"),
+            OptimizeError::InvalidLocks => write!(f, "the locked echoes can't be equipped together, or aren't in the inventory"),
+            OptimizeError::NonFiniteDamage => write!(f, "a build's damage was not a finite number"),
+            OptimizeError::InvalidWeight => write!(f, "weights must be finite and not negative"),
+        }
+    }
+}
+
+impl Error for OptimizeError {}
+
+/// The `Build` struct represents a combination of echoes found by the optimizer
+#[derive(Clone, PartialEq, Debug)]
+pub struct Build {
+    /// The echoes of the build. The main echo, whose active skill is used, comes first, and the rest are in
+    /// inventory order
+    pub echoes: Vec<Echo>,
+    /// Damage dealt with the build, counting the main echo's skill, or the amount healed when optimizing healing
+    pub damage: f64,
+    /// Total cost of the echoes
+    pub cost: isize,
+    /// Final stats with the echoes and their set bonuses
+    pub stats: Stats,
+}
+
+/// The `Constraint` struct represents limits on a stat that builds have to respect to be picked, like a minimum
+/// Energy Regen needed to rotate. The stat is measured on the final stats of a build, with set bonuses.
+///
+/// # Examples
+///
+/// ```
+/// use wuwa_calculator::calculator::Stats;
+/// use wuwa_calculator::optimizer::Constraint;
+///
+/// let energy_regen = Constraint::at_least(|stats| stats.energy_regen, 1.2);
+/// let crit_rate = Constraint::at_most(|stats| stats.crit_rate, 1.0);
+/// let hp = Constraint::between(Stats::hp, 15000.0, 20000.0);
+///
+/// // The stat can be computed from values only known at runtime
+/// let rotation_energy = 1.5;
+/// let energy = Constraint::at_least(move |stats| stats.energy_regen * rotation_energy, 1.8);
+/// ```
+#[derive(Clone)]
+pub struct Constraint {
+    /// Reads the stat, either a field of `Stats` or a stat derived from them like `Stats::hp`. It must never decrease
+    /// when an echo is added to the stats, which holds for every field and derived stat of `Stats`
+    pub stat: Arc<dyn Fn(&Stats) -> f64 + Send + Sync>,
+    /// Lowest value the stat is allowed to have
+    pub min: f64,
+    /// Highest value the stat is allowed to have
+    pub max: f64,
+}
+
+impl Constraint {
+    /// Creates a constraint with only a minimum
+    pub fn at_least(stat: impl Fn(&Stats) -> f64 + Send + Sync + 'static, min: f64) -> Constraint {
+        Constraint::between(stat, min, f64::INFINITY)
+    }
+
+    /// Creates a constraint with only a maximum
+    pub fn at_most(stat: impl Fn(&Stats) -> f64 + Send + Sync + 'static, max: f64) -> Constraint {
+        Constraint::between(stat, f64::NEG_INFINITY, max)
+    }
+
+    /// Creates a constraint with both a minimum and a maximum
+    pub fn between(stat: impl Fn(&Stats) -> f64 + Send + Sync + 'static, min: f64, max: f64) -> Constraint {
+        Constraint {
+            stat: Arc::new(stat),
+            min,
+            max,
+        }
+    }
+
+    /// Checks if the stat is within the limits
+    pub fn allows(&self, stats: &Stats) -> bool {
+        let value = (self.stat)(stats);
+        value >= self.min && value <= self.max
+    }
+}
+
+impl fmt::Debug for Constraint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Constraint")
+            .field("min", &self.min)
+            .field("max", &self.max)
+            .finish_non_exhaustive()
+    }
+}
+
+/// The `Objective` enum represents what the optimizer maximizes for the target it's given
+#[derive(Clone, Default)]
+pub enum Objective {
+    /// Damage of a hit that doesn't crit
+    NonCrit,
+    /// Damage of a hit that crits
+    Crit,
+    /// Expected damage of a hit, given the crit rate
+    #[default]
+    Average,
+    /// Any score computed from the final stats of a build. The target is ignored. The score must never decrease
+    /// when a stat increases, or the optimizer may miss the best build
+    Custom(Arc<dyn Fn(&Stats) -> f64 + Send + Sync>),
+}
+
+impl Objective {
+    /// Scores the stats of a build against a target
+    pub fn score(&self, stats: &Stats, target: Target, character_level: isize, enemy: Enemy) -> f64 {
+        match self {
+            Objective::NonCrit => stats.skill_adjusted_damage_noncrit(target, character_level, enemy),
+            Objective::Crit => stats.skill_adjusted_damage_crit(target, character_level, enemy),
+            Objective::Average => stats.skill_adjusted_damage_average(target, character_level, enemy),
+            Objective::Custom(score) => score(stats),
+        }
+    }
+
+    /// Scores the stats of a build against a rotation: a list of targets, each with the number of times it's hit
+    /// during the rotation as its weight. Weights can't be negative, which the rotation optimizers check
+    pub fn rotation_score(&self, stats: &Stats, rotation: &[(Target, f64)], character_level: isize, enemy: Enemy) -> f64 {
+        rotation.iter()
+            .map(|(target, weight)| weight * self.score(stats, *target, character_level, enemy))
+            .sum()
+    }
+}
+
+impl fmt::Debug for Objective {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Objective::NonCrit => write!(f, "NonCrit"),
+            Objective::Crit => write!(f, "Crit"),
+            Objective::Average => write!(f, "Average"),
+            Objective::Custom(_) => write!(f, "Custom(..)"),
+        }
+    }
+}
+
+/// The `OptimizeOptions` struct holds the settings of a search
+#[derive(Clone, Debug)]
+pub struct OptimizeOptions {
+    /// What builds are ranked by
+    pub objective: Objective,
+    /// Number of worker threads the search is split across. The result is the same for any number of threads
+    pub threads: usize,
+    /// Limits every build has to respect. Builds that break any of them are rejected
+    pub constraints: Vec<Constraint>,
+    /// Indices in the inventory of echoes every build has to include, like a main echo picked for its skill
+    pub locked: Vec<usize>,
+    /// Indices in the inventory of echoes no build can include, like echoes equipped on another character
+    pub excluded: Vec<usize>,
+    /// Times the main echo's active skill is used for every hit of the target, or every rotation. Its damage,
+    /// scored by the objective, is added to the build's. Custom objectives don't count it. It can't be negative
+    pub echo_skill_uses: f64,
+    /// Whether conditional set effects and weapon passives count, at their maximum stacks. Without them, builds are
+    /// compared only on the bonuses that are always active
+    pub conditional: bool,
+}
+
+impl OptimizeOptions {
+    /// Options that search on every core the machine has
+    pub fn parallel() -> OptimizeOptions {
+        OptimizeOptions {
+            threads: thread::available_parallelism().map_or(1, |threads| threads.get()),
+            ..Default::default()
+        }
+    }
+}
+
+impl Default for OptimizeOptions {
+    fn default() -> Self {
+        OptimizeOptions {
+            objective: Objective::default(),
+            threads: 1,
+            constraints: Vec::new(),
+            locked: Vec::new(),
+            excluded: Vec::new(),
+            echo_skill_uses: 1.0,
+            conditional: true,
+        }
+    }
+}
+
+/// The `TeamMember` struct represents a character the team optimizer assigns echoes to
+#[derive(Clone)]
+pub struct TeamMember {
+    /// Stats of the character without echoes
+    pub stats: Stats,
+    /// The character's rotation, as described in `Objective::rotation_score`
+    pub rotation: Vec<(Target, f64)>,
+    pub character_level: isize,
+    /// How much the character's rotation damage counts towards the team's
+    pub weight: f64,
+    /// Settings of the character's own searches. Its locked echoes can't go to anyone else
+    pub options: OptimizeOptions,
+}
+
+// Finds the build of up to 5 echoes that deals the most average damage, counting crits, so partially farmed characters with fewer echoes are supported.
+// The search prunes builds over the cost limit and branches that can't beat the best build so far, but the result is always
+// the exact optimum. On ties, the build with more echoes wins, and then the one that comes first in `echoes`.
+// The damage is calculated off the stats given by the target's `Scaling`, so HP and DEF scalers are optimized for their own stat.
+// Set bonuses of each combination are included, with conditional set effects assumed to be active at their maximum stacks.
+// The echo whose active skill deals the most damage is put first as the main echo, and its skill is used once.
+pub fn optimize(stats: Stats, echoes: &[Echo], target: Target, character_level: isize, enemy: Enemy) -> Result<Vec<Echo>, OptimizeError> {
+    optimize_with(stats, echoes, target, character_level, enemy, &OptimizeOptions::default())
+}
+
+// Same as `optimize`, with the search configured by `options`, such as what to maximize or how many threads to use.
+pub fn optimize_with(stats: Stats, echoes: &[Echo], target: Target, character_level: isize, enemy: Enemy, options: &OptimizeOptions) -> Result<Vec<Echo>, OptimizeError> {
+    let skill_score = echo_skill_score(options, character_level, enemy)?;
+    optimize_by(stats, echoes, |stats| options.objective.score(stats, target, character_level, enemy), skill_score.as_deref(), options)
+        .map(|(_, build)| build)
+}
+
+/// Same as `optimize_with`, but returns the best `count` builds ranked from the most damage to the least, so
+/// near-optimal alternatives can be compared. Unless a constraint has a maximum, only builds that can't fit another
+/// echo from `echoes` are ranked, since adding an echo never lowers the damage. Fewer builds are returned if there
+/// aren't enough combinations.
+pub fn optimize_top(stats: Stats, echoes: &[Echo], target: Target, character_level: isize, enemy: Enemy, count: usize, options: &OptimizeOptions) -> Result<Vec<Build>, OptimizeError> {
+    let skill_score = echo_skill_score(options, character_level, enemy)?;
+    optimize_top_by(stats, echoes, |stats| options.objective.score(stats, target, character_level, enemy), skill_score.as_deref(), count, options)
+}
+
+// Same as `optimize_with`, but maximizes the total damage of a rotation, the weighted list of targets described in
+// `Objective::rotation_score`, so no single skill's DMG bonus is over-invested in.
+pub fn optimize_rotation(stats: Stats, echoes: &[Echo], rotation: &[(Target, f64)], character_level: isize, enemy: Enemy, options: &OptimizeOptions) -> Result<Vec<Echo>, OptimizeError> {
+    validate_rotation(rotation)?;
+    let skill_score = echo_skill_score(options, character_level, enemy)?;
+    optimize_by(stats, echoes, |stats| options.objective.rotation_score(stats, rotation, character_level, enemy), skill_score.as_deref(), options)
+        .map(|(_, build)| build)
+}
+
+// Same as `optimize_top`, but ranks builds by the total damage of a rotation, like `optimize_rotation`.
+pub fn optimize_rotation_top(stats: Stats, echoes: &[Echo], rotation: &[(Target, f64)], character_level: isize, enemy: Enemy, count: usize, options: &OptimizeOptions) -> Result<Vec<Build>, OptimizeError> {
+    validate_rotation(rotation)?;
+    let skill_score = echo_skill_score(options, character_level, enemy)?;
+    optimize_top_by(stats, echoes, |stats| options.objective.rotation_score(stats, rotation, character_level, enemy), skill_score.as_deref(), count, options)
+}
+
+// Same as `optimize_with`, but also picks the best weapon out of `weapons`, comparing the best build of each one.
+// Conditional passive effects count only if `options.conditional` is set, like conditional set effects. Weapons with
+// no build that meets the constraints are skipped, and their error is only returned if that's the case for all of them.
+pub fn optimize_weapons(mut stats: Stats, weapons: &[Weapon], echoes: &[Echo], target: Target, character_level: isize, enemy: Enemy, options: &OptimizeOptions) -> Result<(Weapon, Vec<Echo>), OptimizeError> {
+    let skill_score = echo_skill_score(options, character_level, enemy)?;
+    let mut best: Option<(f64, Weapon, Vec<Echo>)> = None;
+    let mut error = OptimizeError::NotEnoughWeapons;
+    for weapon in weapons {
+        weapon.add_to_stats(&mut stats, options.conditional);
+        let result = optimize_by(stats.clone(), echoes, |stats| options.objective.score(stats, target, character_level, enemy), skill_score.as_deref(), options);
+        weapon.remove_from_stats(&mut stats, options.conditional);
+
+        let (damage, build) = match result {
+            Ok(result) => result,
+            Err(skipped @ (OptimizeError::NoValidCombination | OptimizeError::ConstraintsNotMet)) => {
+                error = skipped;
+                continue;
+            }
+            Err(error) => return Err(error),
+        };
+        if best.as_ref().is_none_or(|(best_damage, _, _)| damage > *best_damage) {
+            best = Some((damage, *weapon, build));
+        }
+    }
+    best.map(|(_, weapon, build)| (weapon, build))
+        .ok_or(error)
+}
+
+// Same as `optimize_with`, but maximizes the amount healed by a heal instead of damage, for support builds. The
+// objective of `options` is ignored, and so are echo skills, since they don't heal.
+pub fn optimize_healing(stats: Stats, echoes: &[Echo], heal: HealTarget, options: &OptimizeOptions) -> Result<Vec<Echo>, OptimizeError> {
+    optimize_by(stats, echoes, |stats| stats.heal_amount(heal), None, options)
+        .map(|(_, build)| build)
+}
+
+// Assigns disjoint builds out of one inventory to every member of a team, maximizing the team's damage: the sum of
+// every member's rotation damage times their weight. Optimizing members one after the other would give the first
+// one all the good echoes, so that's only the starting point, from the highest weight down. Then pairs of members
+// trade echoes: one of them tries its best few builds out of the echoes the rest of the team doesn't use, the other
+// one gets its best build out of what's left, and the trade that helps the team the most is kept, until none helps
+// or after `TEAM_MAX_ROUNDS` rounds. A member's searches are only run again once the echoes it can use change.
+// Searching every assignment at once isn't feasible, so the result may not be the exact optimum.
+// Builds are returned in the order of `members`, with each member's unweighted rotation damage.
+pub fn optimize_team(members: &[TeamMember], echoes: &[Echo], enemy: Enemy) -> Result<Vec<Build>, OptimizeError> {
+    for member in members {
+        validate_rotation(&member.rotation)?;
+        validate_weight(member.weight)?;
+    }
+    let locked: Vec<usize> = members.iter().flat_map(|member| member.options.locked.iter().copied()).collect();
+    if locked.iter().enumerate().any(|(i, index)| locked[..i].contains(index)) {
+        return Err(OptimizeError::InvalidLocks);
+    }
+
+    // Searches a member's best builds scoring more than `floor`, without the echoes in `taken` or locked by other
+    // members. Results are kept by the echoes taken, so trades between other members that leave them the same don't
+    // search again. Fewer builds than asked for only rule out the ones under the floor they were searched with
+    let mut cache: HashMap<_, (f64, Vec<Candidate>)> = HashMap::new();
+    let mut search = |member: usize, taken: &[usize], count: usize, floor: f64| -> Result<Vec<Candidate>, OptimizeError> {
+        let mut taken = taken.to_vec();
+        taken.sort_unstable();
+        let key = (member, taken, count);
+        if let Some((searched, builds)) = cache.get(&key) {
+            if floor >= *searched || builds.len() == count {
+                return Ok(builds.iter().filter(|build| build.value > floor).cloned().collect());
+            }
+        }
+
+        let TeamMember {stats, rotation, character_level, options, ..} = &members[member];
+        let mut options = options.clone();
+        options.excluded.extend(&key.1);
+        for (other, other_member) in members.iter().enumerate() {
+            if other != member {
+                options.excluded.extend(&other_member.options.locked);
+            }
+        }
+        let skill_score = echo_skill_score(&options, *character_level, enemy)?;
+        let score = |stats: &Stats| options.objective.rotation_score(stats, rotation, *character_level, enemy);
+        let builds = match search_top(stats, echoes, score, skill_score.as_deref(), count, floor, &options) {
+            Ok(builds) => builds,
+            Err(OptimizeError::NoValidCombination | OptimizeError::ConstraintsNotMet) if floor > f64::NEG_INFINITY => Vec::new(),
+            Err(error) => return Err(error),
+        };
+        cache.insert(key, (floor, builds.clone()));
+        Ok(builds)
+    };
+    let taken = |builds: &[Option<Candidate>], except: &[usize]| -> Vec<usize> {
+        builds.iter().enumerate()
+            .filter(|(member, _)| !except.contains(member))
+            .flat_map(|(_, build)| build.iter().flat_map(|build| build.indices.iter().copied()))
+            .collect()
+    };
+
+    let mut order: Vec<usize> = (0..members.len()).collect();
+    order.sort_by(|&a, &b| members[b].weight.total_cmp(&members[a].weight));
+    let mut builds: Vec<Option<Candidate>> = members.iter().map(|_| None).collect();
+    for &member in &order {
+        builds[member] = Some(search(member, &taken(&builds, &[member]), 1, f64::NEG_INFINITY)?.remove(0));
+    }
+
+    let team_value = |builds: &[Option<Candidate>]| -> f64 {
+        members.iter().zip(builds)
+            .map(|(member, build)| build.as_ref().map_or(0.0, |build| member.weight * build.value))
+            .sum()
+    };
+    // The score a member's build needs for the pair to make up `needed` with a member of `weight`
+    let floor = |needed: f64, weight: f64| if weight > 0.0 { needed / weight } else { f64::NEG_INFINITY };
+    for _ in 0..TEAM_MAX_ROUNDS {
+        let current = team_value(&builds);
+        let mut best_trade: Option<(f64, Vec<Option<Candidate>>)> = None;
+        let to_beat = |best_trade: &Option<(f64, _)>| best_trade.as_ref().map_or(current + current.abs() * 1e-12, |(best, _)| *best);
+        for first in 0..members.len() {
+            for second in (0..members.len()).filter(|second| *second != first) {
+                // The rest of the team keeps its builds, so the pair has to beat the best trade so far on its own. The
+                // second member can't score more than its best build out of every echo the pair can use, which
+                // bounds what the first one needs
+                let taken = taken(&builds, &[first, second]);
+                let (first_weight, second_weight) = (members[first].weight, members[second].weight);
+                let value = |member: usize| builds[member].as_ref().map_or(0.0, |build| members[member].weight * build.value);
+                let rest = current - value(first) - value(second);
+                let second_best = match search(second, &taken, 1, f64::NEG_INFINITY) {
+                    Ok(mut second_builds) => second_builds.remove(0),
+                    Err(OptimizeError::NoValidCombination | OptimizeError::ConstraintsNotMet) => continue,
+                    Err(error) => return Err(error),
+                };
+                let first_floor = floor(to_beat(&best_trade) - rest - second_weight * second_best.value, first_weight);
+                for first_build in search(first, &taken, TEAM_TRADE_BUILDS, first_floor)? {
+                    let second_floor = floor(to_beat(&best_trade) - rest - first_weight * first_build.value, second_weight);
+                    // When the first build leaves the second member's best one alone, it stays the best
+                    let second_build = if first_build.indices.iter().all(|index| !second_best.indices.contains(index)) {
+                        second_best.clone()
+                    } else {
+                        match search(second, &[taken.as_slice(), &first_build.indices].concat(), 1, second_floor)?.pop() {
+                            Some(second_build) => second_build,
+                            None => continue,
+                        }
+                    };
+                    let mut trade = builds.clone();
+                    trade[first] = Some(first_build);
+                    trade[second] = Some(second_build);
+                    let value = team_value(&trade);
+                    if to_beat(&best_trade) < value {
+                        best_trade = Some((value, trade));
+                    }
+                }
+            }
+        }
+        match best_trade {
+            Some((_, trade)) => builds = trade,
+            None => break,
+        }
+    }
+
+    members.iter().zip(builds.iter().flatten())
+        .map(|(member, build)| {
+            let skill_score = echo_skill_score(&member.options, member.character_level, enemy)?;
+            Ok(build_of(&member.stats, echoes, build, skill_score.as_deref(), member.options.conditional))
         })
-        .max_by(|a, b| a.0.partial_cmp(&b.0).unwrap())
-        .unwrap(); // TODO clean this unwrap -> means iterator was empty, aka either less than 5 echoes or no valid 5 echoes combination
-    [*best_combo.1.0, *best_combo.1.1, *best_combo.1.2, *best_combo.1.3, *best_combo.1.4]
+        .collect()
+}
+
+// A negative weight would make the score decrease as stats grow, and the search's pruning relies on it never
+// decreasing, so it would silently return a wrong build
+fn validate_rotation(rotation: &[(Target, f64)]) -> Result<(), OptimizeError> {
+    rotation.iter().try_for_each(|(_, weight)| validate_weight(*weight))
+}
+
+fn validate_weight(weight: f64) -> Result<(), OptimizeError> {
+    if weight.is_finite() && weight >= 0.0 { Ok(()) } else { Err(OptimizeError::InvalidWeight) }
+}
+
+// Scores the main echo's skill with the objective, as many times as it's used. Custom objectives ignore the target,
+// so they can't score it. The number of uses weighs the skill's damage, so it's validated like any other weight
+fn echo_skill_score(options: &OptimizeOptions, character_level: isize, enemy: Enemy) -> Result<Option<Box<SkillScoreFn<'_>>>, OptimizeError> {
+    validate_weight(options.echo_skill_uses)?;
+    if matches!(options.objective, Objective::Custom(_)) || options.echo_skill_uses == 0.0 {
+        return Ok(None);
+    }
+    Ok(Some(Box::new(move |stats, skill| options.echo_skill_uses * options.objective.score(stats, skill, character_level, enemy))))
+}
+
+fn optimize_by(stats: Stats, echoes: &[Echo], score: impl Fn(&Stats) -> f64 + Sync, skill_score: Option<SkillScore>, options: &OptimizeOptions) -> Result<(f64, Vec<Echo>), OptimizeError> {
+    optimize_top_by(stats, echoes, score, skill_score, 1, options)
+        .map(|mut builds| builds.remove(0))
+        .map(|build| (build.damage, build.echoes))
+}
+
+fn optimize_top_by(stats: Stats, echoes: &[Echo], score: impl Fn(&Stats) -> f64 + Sync, skill_score: Option<SkillScore>, count: usize, options: &OptimizeOptions) -> Result<Vec<Build>, OptimizeError> {
+    Ok(search_top(&stats, echoes, score, skill_score, count.max(1), f64::NEG_INFINITY, options)?
+        .into_iter()
+        .take(count)
+        .map(|candidate| build_of(&stats, echoes, &candidate, skill_score, options.conditional))
+        .collect())
+}
+
+// Searches the best `count` builds scoring more than `floor`, which are never empty
+fn search_top(stats: &Stats, echoes: &[Echo], score: impl Fn(&Stats) -> f64 + Sync, skill_score: Option<SkillScore>, count: usize, floor: f64, options: &OptimizeOptions) -> Result<Vec<Candidate>, OptimizeError> {
+    if echoes.is_empty() {
+        return Err(OptimizeError::NotEnoughEchoes);
+    }
+    let in_inventory = options.locked.iter().chain(&options.excluded).all(|&index| index < echoes.len());
+    if !in_inventory {
+        return Err(OptimizeError::InvalidLocks);
+    }
+    let locked_cost: isize = options.locked.iter().map(|&index| echoes[index].cost).sum();
+    let duplicated = options.locked.iter().enumerate().any(|(i, index)| options.locked[..i].contains(index));
+    let excluded = options.locked.iter().any(|index| options.excluded.contains(index));
+    if options.locked.len() > MAX_ECHOES || locked_cost > MAX_COST || duplicated || excluded {
+        return Err(OptimizeError::InvalidLocks);
+    }
+
+    let best = Search::new(stats.clone(), echoes, &score, skill_score, options).run(count, options.threads, floor)?;
+    if best.is_empty() {
+        let fits = !options.locked.is_empty() || echoes.iter().enumerate()
+            .any(|(index, echo)| !options.excluded.contains(&index) && echo.cost <= MAX_COST);
+        return Err(if fits && !options.constraints.is_empty() { OptimizeError::ConstraintsNotMet } else { OptimizeError::NoValidCombination });
+    }
+    Ok(best)
+}
+
+fn build_of(stats: &Stats, echoes: &[Echo], candidate: &Candidate, skill_score: Option<SkillScore>, conditional: bool) -> Build {
+    let mut build: Vec<Echo> = candidate.indices.iter().map(|&i| echoes[i]).collect();
+    let mut stats = stats.clone();
+    for echo in &build {
+        echo.add_to_stats(&mut stats);
+    }
+    add_set_bonuses(&build, &mut stats, conditional);
+
+    // The main echo is the first one whose skill deals the most damage
+    let main = skill_score.and_then(|skill_score| {
+        build.iter().enumerate()
+            .filter_map(|(i, echo)| echo.skill().map(|skill| (i, skill_score(&stats, skill))))
+            .reduce(|best, skill| if skill.1 > best.1 { skill } else { best })
+    });
+    if let Some((main, _)) = main {
+        let echo = build.remove(main);
+        build.insert(0, echo);
+    }
+    Build {
+        cost: build.iter().map(|echo| echo.cost).sum(),
+        echoes: build,
+        damage: candidate.value,
+        stats,
+    }
 }
@@ -0,0 +1,536 @@
+use std::cmp;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::thread;
+use crate::calculator::{Stats, Target};
+use crate::echo::{Echo, StatType};
+use crate::optimizer::{Constraint, OptimizeError, OptimizeOptions, MAX_COST, MAX_ECHOES};
+use crate::sonata::{add_set_bonuses, set_index, SetBonusBounds, SONATA_SETS};
+
+const STAT_COUNT: usize = StatType::ALL.len();
+
+// Relative slack given to upper bounds before pruning, so floating point rounding can never prune the optimum
+const BOUND_TOLERANCE: f64 = 1e-9;
+
+// Crit DMG rolls are about twice as big as Crit Rate ones, so echoes are ranked by this much Crit Rate plus their Crit
+// DMG to bound both stats together
+const CRIT_RATE_WEIGHT: f64 = 2.0;
+// Number of pieces the trade-off between Crit Rate and Crit DMG is split into when bounding them together
+const CRIT_BOUND_STEPS: usize = 4;
+
+/// Scores the damage of the main echo's active skill on the final stats of a build
+pub(super) type SkillScoreFn<'a> = dyn Fn(&Stats, Target) -> f64 + Sync + 'a;
+pub(super) type SkillScore<'a> = &'a SkillScoreFn<'a>;
+
+/// A build found during the search, with the indices of its echoes in the inventory in ascending order
+#[derive(Clone)]
+pub(super) struct Candidate {
+    pub(super) value: f64,
+    pub(super) indices: Vec<usize>,
+}
+
+impl Candidate {
+    // Orders better builds first. On ties, the build with more echoes wins, and then the one that comes first in
+    // the inventory
+    fn rank(&self, other: &Candidate) -> cmp::Ordering {
+        other.value.partial_cmp(&self.value).unwrap_or(cmp::Ordering::Equal)
+            .then(other.indices.len().cmp(&self.indices.len()))
+            .then(self.indices.cmp(&other.indices))
+    }
+}
+
+/// Echoes that share a cost and a main stat type
+struct Group {
+    cost: isize,
+    // Inventory indices of the echoes, from best to worst on their own
+    echoes: Vec<usize>,
+    // For every position in `echoes` and stat, the sum of the `k` best values that echoes from that position on
+    // give to the stat, for every `k` up to `MAX_ECHOES`. Indexed by `position * STAT_COUNT + stat`
+    best_sums: Vec<[f64; MAX_ECHOES + 1]>,
+    // Same as `best_sums`, for the crit value of the echoes, `CRIT_RATE_WEIGHT` times their Crit Rate plus their Crit
+    // DMG. Indexed by `position`
+    crit_sums: Vec<[f64; MAX_ECHOES + 1]>,
+    // Number of echoes of each set in `SONATA_SETS`
+    set_pieces: [usize; SONATA_SETS.len()],
+}
+
+/// How many echoes of each group a build takes, as the group of each slot. Slots of the same group are contiguous
+struct Skeleton {
+    slots: Vec<usize>,
+    bound: f64,
+}
+
+/// Branch and bound search for the builds of up to `MAX_ECHOES` echoes within the cost limit with the highest scores.
+///
+/// Echoes are grouped by cost and main stat type, and builds are searched one skeleton at a time: how many
+/// echoes of each group they take. Only skeletons that can't fit another echo are searched, since adding an
+/// echo never lowers the score. Skeletons are searched from the one with the highest upper bound down, and
+/// inside one, echoes are tried from the best to the worst on their own, so good builds are found early.
+///
+/// Upper bounds are the score of an optimistic build: for each stat, the best values the echoes still to be
+/// picked could add to it, chosen independently of the other stats. Set bonuses are bounded by the pieces
+/// already picked plus every piece the remaining slots could still pick. Crit Rate and Crit DMG are the exception:
+/// the best Crit Rate and the best Crit DMG echoes are rarely the same ones, so they're also bounded by the best
+/// crit value the echoes could add, and the score is bounded on every piece of the trade-off between the two.
+/// Whenever even that can't beat the worst of the best builds kept so far, the rest of the branch is pruned. This assumes the score
+/// never decreases when a stat increases, which holds for every damage and healing formula.
+///
+/// Builds are only kept if they meet every constraint. The optimistic stats of a branch also bound what its builds
+/// can reach, so branches that can't reach a minimum are pruned, and branches whose echoes already go over a
+/// maximum are too. A maximum can make a build that could fit another echo the best one, so with maximums every
+/// skeleton is searched, not just the ones that can't fit another echo.
+///
+/// Locked echoes are part of every build, so the search starts from the base stats with them, and only fills the
+/// slots and cost they leave. Excluded echoes aren't put in any group.
+///
+/// When echo skills are scored, the main echo of a build is the one whose skill deals the most damage, and its
+/// damage is added to the build's score. It's bounded by the damage of the best skill of any echo that can be picked
+/// on the optimistic stats. Copies of the same monster only count once towards set bonuses, so counting every
+/// piece still bounds them.
+///
+/// Every build is scored by adding its echoes to the base stats in inventory order, so a build's score doesn't
+/// depend on the order it was found in.
+///
+/// Skeletons can be split across several worker threads, each with its own stats and best builds, sharing only the
+/// score to beat for pruning. Since a branch is only pruned when it can't even tie that score, every build that
+/// makes the final ranking is still scored by some worker, and merging their best builds gives the same result as
+/// searching on a single thread.
+pub(super) struct Search<'a, F: Fn(&Stats) -> f64> {
+    base: Stats,
+    echoes: &'a [Echo],
+    score: &'a F,
+    skill_score: Option<SkillScore<'a>>,
+    // Distinct active skills of the echoes builds can have
+    skills: Vec<Target>,
+    groups: Vec<Group>,
+    // Stats any echo or set bonus can increase
+    stats: Vec<usize>,
+    set_bonuses: SetBonusBounds,
+    // Index in `SONATA_SETS` of the set of each echo
+    echo_sets: Vec<Option<usize>>,
+    constraints: &'a [Constraint],
+    locked: &'a [usize],
+    // Whether conditional set effects are counted
+    conditional: bool,
+    // The base stats with the locked echoes, and the pieces of each set and cost they take
+    locked_stats: Stats,
+    locked_pieces: [usize; SONATA_SETS.len()],
+    locked_cost: isize,
+}
+
+/// The state of one worker thread of a search
+struct Worker<'s, 'a, F: Fn(&Stats) -> f64> {
+    search: &'s Search<'a, F>,
+    // The best builds found by this worker, from best to worst
+    best: Vec<Candidate>,
+    count: usize,
+    // Builds that don't score more than this aren't kept at all
+    floor: f64,
+    // Bits of the score a build has to beat to be kept, the highest `count`th best score of any worker so far or the
+    // floor
+    threshold: &'s AtomicU64,
+}
+
+impl<'a, F: Fn(&Stats) -> f64 + Sync> Search<'a, F> {
+    pub(super) fn new(base: Stats, echoes: &'a [Echo], score: &'a F, skill_score: Option<SkillScore<'a>>, options: &'a OptimizeOptions) -> Search<'a, F> {
+        let single_scores: Vec<f64> = echoes.iter()
+            .map(|echo| {
+                let mut stats = base.clone();
+                echo.add_to_stats(&mut stats);
+                score(&stats)
+            })
+            .collect();
+        let echo_values: Vec<[f64; STAT_COUNT]> = echoes.iter()
+            .map(|echo| {
+                let mut values = [0.0; STAT_COUNT];
+                let stats = [(echo.main_stat_type, echo.main_stat_value), (echo.secondary_stat_type, echo.secondary_stat_value)];
+                for (stat_type, value) in stats.iter().chain(echo.sub_stats.iter()) {
+                    if *stat_type != StatType::None {
+                        values[*stat_type as usize] += value;
+                    }
+                }
+                values
+            })
+            .collect();
+
+        let mut groups: Vec<Group> = Vec::new();
+        let mut group_keys: Vec<(isize, StatType)> = Vec::new();
+        for (index, echo) in echoes.iter().enumerate() {
+            if options.locked.contains(&index) || options.excluded.contains(&index) {
+                continue;
+            }
+            let key = (echo.cost, echo.main_stat_type);
+            let group = match group_keys.iter().position(|group_key| *group_key == key) {
+                Some(group) => group,
+                None => {
+                    group_keys.push(key);
+                    groups.push(Group {
+                        cost: echo.cost,
+                        echoes: Vec::new(),
+                        best_sums: Vec::new(),
+                        crit_sums: Vec::new(),
+                        set_pieces: [0; SONATA_SETS.len()],
+                    });
+                    groups.len() - 1
+                }
+            };
+            groups[group].echoes.push(index);
+            if let Some(set) = set_index(echo.sonata) {
+                groups[group].set_pieces[set] += 1;
+            }
+        }
+
+        // Skeletons pick the echoes of the groups in this order. Expensive echoes make the biggest difference, and
+        // branching over the groups with the most echoes last lets the bounds prune them once the rest are picked
+        groups.sort_by_key(|group| (cmp::Reverse(group.cost), group.echoes.len()));
+
+        for group in groups.iter_mut() {
+            group.echoes.sort_by(|&a, &b| single_scores[b].total_cmp(&single_scores[a]).then(a.cmp(&b)));
+
+            // Best values are built from the last position backwards, inserting each echo into the ones after it
+            let mut best_values = vec![[0.0f64; MAX_ECHOES]; (group.echoes.len() + 1) * STAT_COUNT];
+            for position in (0..group.echoes.len()).rev() {
+                let (current, next) = best_values.split_at_mut((position + 1) * STAT_COUNT);
+                let current = &mut current[position * STAT_COUNT..];
+                current.copy_from_slice(&next[..STAT_COUNT]);
+
+                for (values, value) in current.iter_mut().zip(echo_values[group.echoes[position]]) {
+                    if value > values[MAX_ECHOES - 1] {
+                        values[MAX_ECHOES - 1] = value;
+                        values.sort_by(|a, b| b.total_cmp(a));
+                    }
+                }
+            }
+            group.best_sums = best_values.iter().map(prefix_sums).collect();
+
+            let mut best_crit = vec![[0.0f64; MAX_ECHOES]; group.echoes.len() + 1];
+            for position in (0..group.echoes.len()).rev() {
+                let values = echo_values[group.echoes[position]];
+                let crit = CRIT_RATE_WEIGHT * values[StatType::CritRate as usize] + values[StatType::CritDmg as usize];
+                best_crit[position] = best_crit[position + 1];
+                if crit > best_crit[position][MAX_ECHOES - 1] {
+                    best_crit[position][MAX_ECHOES - 1] = crit;
+                    best_crit[position].sort_by(|a, b| b.total_cmp(a));
+                }
+            }
+            group.crit_sums = best_crit.iter().map(prefix_sums).collect();
+        }
+
+        let set_bonuses = SetBonusBounds::new(options.conditional);
+        let echo_sets: Vec<Option<usize>> = echoes.iter().map(|echo| set_index(echo.sonata)).collect();
+        let max_set_bonuses = set_bonuses.max_bonuses(&[MAX_ECHOES; SONATA_SETS.len()]);
+        let stats = (0..STAT_COUNT)
+            .filter(|&stat| max_set_bonuses[stat] > 0.0 || echo_values.iter().any(|values| values[stat] > 0.0))
+            .collect();
+
+        let mut locked_stats = base.clone();
+        let mut locked_pieces = [0; SONATA_SETS.len()];
+        for &index in &options.locked {
+            echoes[index].add_to_stats(&mut locked_stats);
+            if let Some(set) = echo_sets[index] {
+                locked_pieces[set] += 1;
+            }
+        }
+
+        let mut skills: Vec<Target> = Vec::new();
+        if skill_score.is_some() {
+            for (index, echo) in echoes.iter().enumerate() {
+                match echo.skill() {
+                    Some(skill) if !options.excluded.contains(&index) && !skills.contains(&skill) => skills.push(skill),
+                    _ => (),
+                }
+            }
+        }
+
+        Search {
+            base,
+            echoes,
+            score,
+            skill_score,
+            skills,
+            groups,
+            stats,
+            set_bonuses,
+            echo_sets,
+            constraints: &options.constraints,
+            locked: &options.locked,
+            conditional: options.conditional,
+            locked_stats,
+            locked_pieces,
+            locked_cost: options.locked.iter().map(|&index| echoes[index].cost).sum(),
+        }
+    }
+
+    /// Searches every build on `threads` worker threads, returning the best `count` ones that score more than `floor`
+    /// from best to worst
+    pub(super) fn run(&self, count: usize, threads: usize, floor: f64) -> Result<Vec<Candidate>, OptimizeError> {
+        let mut skeletons = Vec::new();
+        self.skeletons(0, &mut Vec::new(), &mut skeletons);
+        skeletons.sort_by(|a, b| b.bound.total_cmp(&a.bound));
+
+        // Workers take the next skeleton that hasn't been searched yet, so the ones with the highest bounds go first
+        let next = AtomicUsize::new(0);
+        let threshold = AtomicU64::new(floor.to_bits());
+        let work = || -> Result<Vec<Candidate>, OptimizeError> {
+            let mut worker = Worker {search: self, best: Vec::with_capacity(count + 1), count, floor, threshold: &threshold};
+            while let Some(skeleton) = skeletons.get(next.fetch_add(1, Ordering::Relaxed)) {
+                if worker.prunes(skeleton.bound) {
+                    break;
+                }
+                worker.fill(skeleton, 0, 0, &mut Vec::with_capacity(MAX_ECHOES), &self.locked_stats.clone(), self.locked_pieces)?;
+            }
+            Ok(worker.best)
+        };
+
+        if threads <= 1 {
+            return work();
+        }
+        let results: Vec<_> = thread::scope(|scope| {
+            let workers: Vec<_> = (0..threads).map(|_| scope.spawn(work)).collect();
+            workers.into_iter().map(|worker| worker.join().expect("optimizer worker panicked")).collect()
+        });
+
+        let mut best: Vec<Candidate> = Vec::new();
+        for result in results {
+            best.extend(result?);
+        }
+        best.sort_by(Candidate::rank);
+        best.truncate(count);
+        Ok(best)
+    }
+
+    // Collects every skeleton that can't fit another echo, given the number of echoes already taken from the
+    // groups before `group`
+    fn skeletons(&self, group: usize, counts: &mut Vec<usize>, skeletons: &mut Vec<Skeleton>) {
+        let slots = MAX_ECHOES - self.locked.len() - counts.iter().sum::<usize>();
+        let budget = MAX_COST - self.locked_cost - counts.iter().zip(&self.groups).map(|(count, group)| *count as isize * group.cost).sum::<isize>();
+
+        if group == self.groups.len() {
+            let fits_another = slots > 0 && self.groups.iter().zip(counts.iter())
+                .any(|(group, count)| *count < group.echoes.len() && group.cost <= budget);
+            let has_maximum = self.constraints.iter().any(|constraint| constraint.max < f64::INFINITY);
+            let empty = counts.iter().all(|count| *count == 0) && self.locked.is_empty();
+            if (!fits_another || has_maximum) && !empty {
+                skeletons.extend(self.skeleton(counts));
+            }
+            return;
+        }
+
+        let cost = self.groups[group].cost;
+        let fit = if cost > 0 { (budget.max(0) / cost) as usize } else { slots };
+        let max_count = self.groups[group].echoes.len().min(slots).min(fit);
+        for count in (0..=max_count).rev() {
+            counts.push(count);
+            self.skeletons(group + 1, counts, skeletons);
+            counts.pop();
+        }
+    }
+
+    // Returns `None` if no build of the skeleton can reach the minimums of the constraints
+    fn skeleton(&self, counts: &[usize]) -> Option<Skeleton> {
+        let mut max_pieces = self.locked_pieces;
+        for (group, count) in self.groups.iter().zip(counts) {
+            for (max_pieces, pieces) in max_pieces.iter_mut().zip(group.set_pieces) {
+                *max_pieces += pieces.min(*count);
+            }
+        }
+        let set_bonuses = self.set_bonuses.max_bonuses(&max_pieces);
+
+        let mut optimistic = self.locked_stats.clone();
+        let mut best_values = [0.0; STAT_COUNT];
+        for &stat in &self.stats {
+            best_values[stat] = self.groups.iter().zip(counts)
+                .map(|(group, count)| group.best_sums[stat][*count])
+                .sum::<f64>();
+            StatType::ALL[stat].add_to_stats(&mut optimistic, best_values[stat] + set_bonuses[stat]);
+        }
+        let best_crit = self.groups.iter().zip(counts)
+            .map(|(group, count)| group.crit_sums[0][*count])
+            .sum::<f64>();
+
+        if !self.reachable(&optimistic) {
+            return None;
+        }
+        Some(Skeleton {
+            slots: counts.iter().enumerate().flat_map(|(group, count)| std::iter::repeat_n(group, *count)).collect(),
+            bound: self.bound(&optimistic, &best_values, best_crit),
+        })
+    }
+
+    // Upper bound of the score of builds whose stats are at most `optimistic`, where the echoes still to be picked
+    // add at most `best_values` to each stat, and at most `best_crit` crit value.
+    //
+    // The Crit Rate `x` and Crit DMG `y` these echoes add lie under both of their best values and the line
+    // `CRIT_RATE_WEIGHT * x + y = best_crit`. The Crit Rate range is split into pieces, and on each one Crit Rate is
+    // at most the end of the piece and Crit DMG at most the line's value at its start, so the highest score on any
+    // piece bounds every build, whatever the shape of the score
+    fn bound(&self, optimistic: &Stats, best_values: &[f64; STAT_COUNT], best_crit: f64) -> f64 {
+        let (crit_rate, crit_dmg) = (best_values[StatType::CritRate as usize], best_values[StatType::CritDmg as usize]);
+        let low = ((best_crit - crit_dmg) / CRIT_RATE_WEIGHT).max(0.0);
+        if CRIT_RATE_WEIGHT * crit_rate + crit_dmg <= best_crit || low >= crit_rate {
+            return self.optimistic_score(optimistic);
+        }
+
+        let step = (crit_rate - low) / CRIT_BOUND_STEPS as f64;
+        let mut stats = optimistic.clone();
+        let mut bound = f64::NEG_INFINITY;
+        for piece in 0..CRIT_BOUND_STEPS {
+            let start = low + step * piece as f64;
+            let end = if piece + 1 == CRIT_BOUND_STEPS { crit_rate } else { start + step };
+            stats.crit_rate = optimistic.crit_rate - (crit_rate - end);
+            stats.crit_dmg = optimistic.crit_dmg - (crit_dmg - (best_crit - CRIT_RATE_WEIGHT * start).min(crit_dmg));
+            bound = bound.max(self.optimistic_score(&stats));
+        }
+        bound
+    }
+
+    fn optimistic_score(&self, optimistic: &Stats) -> f64 {
+        (self.score)(optimistic) + self.main_skill_score(optimistic, self.skills.iter().copied())
+    }
+
+    // Score of the best of `skills` on `stats`, the one the main echo should have, or 0 if there's none
+    fn main_skill_score(&self, stats: &Stats, skills: impl Iterator<Item = Target>) -> f64 {
+        match self.skill_score {
+            Some(skill_score) => skills.map(|skill| skill_score(stats, skill)).fold(0.0, f64::max),
+            None => 0.0,
+        }
+    }
+
+    // Whether optimistic stats reach the minimum of every constraint
+    fn reachable(&self, optimistic: &Stats) -> bool {
+        self.constraints.iter().all(|constraint| (constraint.stat)(optimistic) >= constraint.min)
+    }
+}
+
+impl<F: Fn(&Stats) -> f64 + Sync> Worker<'_, '_, F> {
+    fn threshold(&self) -> f64 {
+        f64::from_bits(self.threshold.load(Ordering::Relaxed))
+    }
+
+    fn prunes(&self, bound: f64) -> bool {
+        let threshold = self.threshold();
+        bound < threshold - threshold.abs() * BOUND_TOLERANCE
+    }
+
+    // Picks the echo of every slot of a skeleton from `slot` on, trying echoes of the slot's group from `start` on.
+    // `stats` are the base stats with the locked echoes and the echoes of `build`, without set bonuses.
+    fn fill(&mut self, skeleton: &Skeleton, slot: usize, start: usize, build: &mut Vec<usize>, stats: &Stats, pieces: [usize; SONATA_SETS.len()]) -> Result<(), OptimizeError> {
+        if self.search.constraints.iter().any(|constraint| (constraint.stat)(stats) > constraint.max) {
+            return Ok(());
+        }
+        if slot == skeleton.slots.len() {
+            return self.evaluate(build);
+        }
+
+        let group = skeleton.slots[slot];
+        let remaining = skeleton.slots[slot..].iter().take_while(|group| **group == skeleton.slots[slot]).count();
+
+        // What the slots of the other groups can add at best doesn't change while picking this one. Set bonuses are
+        // bounded by the pieces already picked and the ones the remaining slots could still pick
+        let mut rest = [0.0; STAT_COUNT];
+        let mut rest_crit = 0.0;
+        let mut max_pieces = pieces;
+        for (max_pieces, group_pieces) in max_pieces.iter_mut().zip(self.search.groups[group].set_pieces) {
+            *max_pieces += group_pieces.min(remaining);
+        }
+        for (rest_slot, &rest_group) in skeleton.slots.iter().enumerate().skip(slot + remaining) {
+            if rest_slot == 0 || skeleton.slots[rest_slot - 1] != rest_group {
+                let count = skeleton.slots[rest_slot..].iter().take_while(|group| **group == rest_group).count();
+                for &stat in &self.search.stats {
+                    rest[stat] += self.search.groups[rest_group].best_sums[stat][count];
+                }
+                rest_crit += self.search.groups[rest_group].crit_sums[0][count];
+                for (max_pieces, group_pieces) in max_pieces.iter_mut().zip(self.search.groups[rest_group].set_pieces) {
+                    *max_pieces += group_pieces.min(count);
+                }
+            }
+        }
+        let set_bonuses = self.search.set_bonuses.max_bonuses(&max_pieces);
+        let mut fixed = stats.clone();
+        for &stat in &self.search.stats {
+            StatType::ALL[stat].add_to_stats(&mut fixed, rest[stat] + set_bonuses[stat]);
+        }
+
+        let mut optimistic = fixed.clone();
+        let mut best_values = rest;
+        for position in start..=(self.search.groups[group].echoes.len() - remaining) {
+            if self.threshold() > f64::NEG_INFINITY || !self.search.constraints.is_empty() {
+                optimistic.clone_from(&fixed);
+                for &stat in &self.search.stats {
+                    let best_value = self.search.groups[group].best_sums[position * STAT_COUNT + stat][remaining];
+                    StatType::ALL[stat].add_to_stats(&mut optimistic, best_value);
+                    best_values[stat] = rest[stat] + best_value;
+                }
+                let best_crit = rest_crit + self.search.groups[group].crit_sums[position][remaining];
+                if !self.search.reachable(&optimistic) || self.prunes(self.search.bound(&optimistic, &best_values, best_crit)) {
+                    break;
+                }
+            }
+
+            let index = self.search.groups[group].echoes[position];
+            let mut build_stats = stats.clone();
+            self.search.echoes[index].add_to_stats(&mut build_stats);
+            let mut build_pieces = pieces;
+            if let Some(set) = self.search.echo_sets[index] {
+                build_pieces[set] += 1;
+            }
+            build.push(index);
+            let next_start = if remaining > 1 { position + 1 } else { 0 };
+            self.fill(skeleton, slot + 1, next_start, build, &build_stats, build_pieces)?;
+            build.pop();
+        }
+        Ok(())
+    }
+
+    // Scores a build and keeps it if it beats one of the best ones so far
+    fn evaluate(&mut self, build: &[usize]) -> Result<(), OptimizeError> {
+        let mut indices = [build, self.search.locked].concat();
+        indices.sort();
+
+        let mut stats = self.search.base.clone();
+        for &index in &indices {
+            self.search.echoes[index].add_to_stats(&mut stats);
+        }
+        let echoes: Vec<Echo> = indices.iter().map(|&index| self.search.echoes[index]).collect();
+        add_set_bonuses(&echoes, &mut stats, self.search.conditional);
+        if !self.search.constraints.iter().all(|constraint| constraint.allows(&stats)) {
+            return Ok(());
+        }
+        let skills = echoes.iter().filter_map(Echo::skill);
+        let value = (self.search.score)(&stats) + self.search.main_skill_score(&stats, skills);
+
+        if !value.is_finite() {
+            return Err(OptimizeError::NonFiniteDamage);
+        }
+        if value <= self.floor {
+            return Ok(());
+        }
+        let candidate = Candidate {value, indices};
+        let rank = self.best.iter().position(|best| candidate.rank(best).is_lt()).unwrap_or(self.best.len());
+        if rank >= self.count {
+            return Ok(());
+        }
+        self.best.insert(rank, candidate);
+        self.best.truncate(self.count);
+
+        if self.best.len() == self.count {
+            let value = self.best[self.count - 1].value;
+            let mut threshold = self.threshold.load(Ordering::Relaxed);
+            while f64::from_bits(threshold) < value {
+                match self.threshold.compare_exchange_weak(threshold, value.to_bits(), Ordering::Relaxed, Ordering::Relaxed) {
+                    Ok(_) => break,
+                    Err(current) => threshold = current,
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+// Sums of the first `k` values, for every `k` up to `MAX_ECHOES`
+fn prefix_sums(values: &[f64; MAX_ECHOES]) -> [f64; MAX_ECHOES + 1] {
+    let mut sums = [0.0; MAX_ECHOES + 1];
+    for k in 0..MAX_ECHOES {
+        sums[k + 1] = sums[k] + values[k];
+    }
+    sums
+}
@@ -0,0 +1,787 @@
+use std::sync::Arc;
+use crate::calculator::{BaseStats, Enemy, HealTarget, Scaling, Stats, Target};
+use crate::{Element, SkillType};
+use crate::echo::{Echo, EchoClass, EchoIdentity, StatType};
+use crate::sonata::{add_set_bonuses, Sonata};
+use crate::weapon::{Weapon, HELIOS_CLEAVER, VERDANT_SUMMIT};
+use crate::optimizer::{optimize, optimize_by, optimize_healing, optimize_rotation, optimize_rotation_top, optimize_team, optimize_top, optimize_weapons, optimize_with, Build, Constraint, Objective, OptimizeError, OptimizeOptions, TeamMember, MAX_COST, MAX_ECHOES};
+
+fn echo(cost: isize, main_stat_type: StatType, main_stat_value: f64) -> Echo {
+    let (secondary_stat_type, secondary_stat_value) = match cost {
+        1 => (StatType::HpFlat, 2280.0),
+        3 => (StatType::AtkFlat, 100.0),
+        _ => (StatType::AtkFlat, 150.0),
+    };
+    Echo {
+        cost,
+        rarity: 5,
+        sonata: Sonata::None,
+        identity: None,
+        main_stat_type,
+        main_stat_value,
+        secondary_stat_type,
+        secondary_stat_value,
+        sub_stats: [(StatType::None, 0.0); 5],
+    }
+}
+
+fn inventory() -> Vec<Echo> {
+    vec![
+        echo(4, StatType::AtkMult, 0.33),
+        echo(4, StatType::HpMult, 0.33),
+        echo(3, StatType::AtkMult, 0.30),
+        echo(3, StatType::HpMult, 0.30),
+        echo(3, StatType::AtkMult, 0.30),
+        echo(3, StatType::HpMult, 0.30),
+        echo(1, StatType::AtkMult, 0.18),
+        echo(1, StatType::HpMult, 0.228),
+        echo(1, StatType::AtkMult, 0.18),
+        echo(1, StatType::HpMult, 0.228),
+    ]
+}
+
+fn count_main_stat(build: &[Echo], stat_type: StatType) -> usize {
+    build.iter().filter(|echo| echo.main_stat_type == stat_type).count()
+}
+
+#[test]
+fn test_optimize_follows_target_scaling() {
+    let stats = Stats::new_from_base(BaseStats {hp: 10000.0, atk: 400.0, def: 1000.0});
+    let echoes = inventory();
+
+    let mut target = Target {
+        element: Element::Spectro,
+        skill_type: SkillType::Skill,
+        skill_multiplier: 1.0,
+        skill_scaling_bonus: 1.0,
+        scaling: Scaling::ATK,
+    };
+    let build = optimize(stats.clone(), &echoes, target, 90, Enemy::new(90)).unwrap();
+    assert_eq!(count_main_stat(&build, StatType::AtkMult), 5);
+
+    target.scaling = Scaling::HP;
+    let build = optimize(stats.clone(), &echoes, target, 90, Enemy::new(90)).unwrap();
+    assert_eq!(count_main_stat(&build, StatType::HpMult), 5);
+}
+
+#[test]
+fn test_optimize_objectives() {
+    let stats = Stats::new_from_base(BaseStats {hp: 10000.0, atk: 400.0, def: 1000.0});
+    let target = Target {
+        element: Element::Spectro,
+        skill_type: SkillType::Skill,
+        skill_multiplier: 1.0,
+        skill_scaling_bonus: 1.0,
+        scaling: Scaling::ATK,
+    };
+    let mut crit = echo(4, StatType::AtkMult, 0.33);
+    crit.sub_stats[0] = (StatType::CritRate, 0.105);
+    crit.sub_stats[1] = (StatType::CritDmg, 0.21);
+    let mut atk = echo(4, StatType::AtkMult, 0.33);
+    atk.sub_stats[0] = (StatType::AtkFlat, 30.0);
+    let echoes = [crit, atk, atk, atk];
+
+    // Only three of the echoes fit, and crit substats are worth nothing to hits that don't crit
+    let objective_build = |objective: Objective| {
+        let options = OptimizeOptions {objective, ..Default::default()};
+        optimize_with(stats.clone(), &echoes, target, 90, Enemy::new(90), &options).unwrap()
+    };
+    assert_eq!(objective_build(Objective::NonCrit), vec![atk, atk, atk]);
+    assert_eq!(objective_build(Objective::Crit), vec![crit, atk, atk]);
+    assert_eq!(objective_build(Objective::Average), vec![crit, atk, atk]);
+    assert_eq!(optimize(stats.clone(), &echoes, target, 90, Enemy::new(90)).unwrap(), vec![crit, atk, atk]);
+    assert_eq!(objective_build(Objective::Custom(Arc::new(|stats: &Stats| stats.atk()))), vec![atk, atk, atk]);
+}
+
+#[test]
+fn test_optimize_rotation() {
+    let stats = Stats::new_from_base(BaseStats {hp: 10000.0, atk: 400.0, def: 1000.0});
+    let basic = Target {
+        element: Element::Aero,
+        skill_type: SkillType::Basic,
+        skill_multiplier: 0.5,
+        skill_scaling_bonus: 1.0,
+        scaling: Scaling::ATK,
+    };
+    let heavy = Target {
+        skill_type: SkillType::Heavy,
+        skill_multiplier: 1.5,
+        ..basic
+    };
+    let mut basic_echo = echo(4, StatType::AtkMult, 0.33);
+    basic_echo.sub_stats[0] = (StatType::BasicDmg, 0.116);
+    let mut heavy_echo = echo(4, StatType::AtkMult, 0.33);
+    heavy_echo.sub_stats[0] = (StatType::HeavyDmg, 0.116);
+    let echoes = [basic_echo, basic_echo, heavy_echo, heavy_echo];
+    let options = OptimizeOptions::default();
+
+    let build = optimize_rotation(stats.clone(), &echoes, &[(basic, 1.0)], 90, Enemy::new(90), &options).unwrap();
+    assert_eq!(build, vec![basic_echo, basic_echo, heavy_echo]);
+
+    // Basic attacks hit more often, but heavy attacks deal most of the rotation's damage
+    let rotation = [(basic, 2.0), (heavy, 1.0)];
+    let build = optimize_rotation(stats.clone(), &echoes, &rotation, 90, Enemy::new(90), &options).unwrap();
+    assert_eq!(build, vec![basic_echo, heavy_echo, heavy_echo]);
+
+    let expected = 2.0 * stats.skill_adjusted_damage_average(basic, 90, Enemy::new(90)) + stats.skill_adjusted_damage_average(heavy, 90, Enemy::new(90));
+    assert!((Objective::Average.rotation_score(&stats, &rotation, 90, Enemy::new(90)) - expected).abs() < 1e-9);
+
+    // A negative weight would reward builds for dealing less damage, which the search can't handle
+    for weight in [-1.0, f64::NAN, f64::INFINITY] {
+        let rotation = [(basic, 2.0), (heavy, weight)];
+        assert_eq!(optimize_rotation(stats.clone(), &echoes, &rotation, 90, Enemy::new(90), &options), Err(OptimizeError::InvalidWeight));
+        assert_eq!(optimize_rotation_top(stats.clone(), &echoes, &rotation, 90, Enemy::new(90), 3, &options), Err(OptimizeError::InvalidWeight));
+    }
+}
+
+#[test]
+fn test_optimize_healing() {
+    let stats = Stats::new_from_base(BaseStats {hp: 10000.0, atk: 400.0, def: 1000.0});
+    let mut echoes = inventory();
+    echoes.push(echo(4, StatType::Healing, 0.264));
+
+    let heal = HealTarget {
+        multiplier: 0.2,
+        flat: 500.0,
+        scaling: Scaling::HP,
+    };
+    let build = optimize_healing(stats.clone(), &echoes, heal, &OptimizeOptions::default()).unwrap();
+    assert_eq!(count_main_stat(&build, StatType::Healing), 1);
+    assert_eq!(count_main_stat(&build, StatType::HpMult), 4);
+
+    // Supports often need Energy Regen to rotate, which costs them an HP% echo
+    echoes.push(echo(3, StatType::EnergyRegen, 0.32));
+    let options = OptimizeOptions {
+        constraints: vec![Constraint::at_least(|stats| stats.energy_regen, 1.3)],
+        ..Default::default()
+    };
+    let build = optimize_healing(stats.clone(), &echoes, heal, &options).unwrap();
+    assert_eq!(count_main_stat(&build, StatType::EnergyRegen), 1);
+    assert_eq!(count_main_stat(&build, StatType::HpMult), 3);
+
+    let options = OptimizeOptions {
+        excluded: vec![echoes.len() - 2],
+        ..Default::default()
+    };
+    let build = optimize_healing(stats, &echoes, heal, &options).unwrap();
+    assert_eq!(count_main_stat(&build, StatType::Healing), 0);
+}
+
+#[test]
+fn test_optimize_applies_set_bonuses() {
+    let stats = Stats::new_from_base(BaseStats {hp: 10000.0, atk: 400.0, def: 1000.0});
+    let mut echoes: Vec<Echo> = inventory().into_iter()
+        .filter(|echo| echo.main_stat_type == StatType::AtkMult)
+        .collect();
+    // Same echoes again, but only these complete the Sun-sinking Eclipse 5-piece
+    let with_set: Vec<Echo> = echoes.iter()
+        .map(|echo| Echo { sonata: Sonata::SunSinkingEclipse, ..*echo })
+        .collect();
+    echoes.extend(with_set);
+
+    let target = Target {
+        element: Element::Havoc,
+        skill_type: SkillType::Basic,
+        skill_multiplier: 1.0,
+        skill_scaling_bonus: 1.0,
+        scaling: Scaling::ATK,
+    };
+    let build = optimize(stats.clone(), &echoes, target, 90, Enemy::new(90)).unwrap();
+    assert!(build.iter().all(|echo| echo.sonata == Sonata::SunSinkingEclipse));
+
+    // Without conditional effects, the stacking 5-piece bonus is worth nothing over the 2-piece one
+    let options = OptimizeOptions {conditional: false, ..Default::default()};
+    let build = optimize_top(stats, &echoes, target, 90, Enemy::new(90), 1, &options).unwrap().remove(0);
+    assert!(build.echoes.iter().filter(|echo| echo.sonata == Sonata::SunSinkingEclipse).count() >= 2);
+    assert_eq!(build.stats.element_dmg[Element::Havoc as usize], 0.10);
+}
+
+#[test]
+fn test_optimize_weapons() {
+    let mut stats = Stats::new_from_base(BaseStats {hp: 10000.0, atk: 400.0, def: 1000.0});
+    let echoes = inventory();
+    let weapons = [
+        Weapon::new(&VERDANT_SUMMIT, 60, 3, 1).unwrap(),
+        Weapon::new(&HELIOS_CLEAVER, 70, 4, 1).unwrap(),
+    ];
+
+    let mut target = Target {
+        element: Element::Aero,
+        skill_type: SkillType::Heavy,
+        skill_multiplier: 1.0,
+        skill_scaling_bonus: 1.0,
+        scaling: Scaling::ATK,
+    };
+    let (weapon, _) = optimize_weapons(stats.clone(), &weapons, &echoes, target, 90, Enemy::new(90), &OptimizeOptions::default()).unwrap();
+    assert_eq!(weapon.data.name, VERDANT_SUMMIT.name);
+
+    // Verdant Summit's stacking bonus only applies to Heavy Attacks, a character with plenty of Liberation DMG
+    // bonus gets little out of its Attribute DMG bonus, and its Crit DMG is worth little without Crit Rate
+    StatType::LiberationDmg.add_to_stats(&mut stats, 1.5);
+    stats.crit_rate = 0.0;
+    target.skill_type = SkillType::Liberation;
+    let (weapon, _) = optimize_weapons(stats.clone(), &weapons, &echoes, target, 90, Enemy::new(90), &OptimizeOptions::default()).unwrap();
+    assert_eq!(weapon.data.name, HELIOS_CLEAVER.name);
+
+    // Weapons are compared with the objective of the options, and their builds respect the locks
+    let options = OptimizeOptions {
+        objective: Objective::Custom(Arc::new(|stats: &Stats| stats.crit_dmg)),
+        locked: vec![1],
+        ..Default::default()
+    };
+    let (weapon, build) = optimize_weapons(stats.clone(), &weapons, &echoes, target, 90, Enemy::new(90), &options).unwrap();
+    assert_eq!(weapon.data.name, VERDANT_SUMMIT.name);
+    assert!(build.contains(&echoes[1]));
+
+    // Only Verdant Summit's Crit DMG can reach the constraint, so Helios Cleaver is skipped in either order, and the
+    // error is only returned when no weapon can meet it
+    let options = OptimizeOptions {constraints: vec![Constraint::at_least(|stats| stats.crit_dmg, 1.8)], ..Default::default()};
+    for weapons in [[weapons[0], weapons[1]], [weapons[1], weapons[0]]] {
+        let (weapon, _) = optimize_weapons(stats.clone(), &weapons, &echoes, target, 90, Enemy::new(90), &options).unwrap();
+        assert_eq!(weapon.data.name, VERDANT_SUMMIT.name);
+    }
+    assert!(matches!(optimize_weapons(stats.clone(), &weapons[1..], &echoes, target, 90, Enemy::new(90), &options), Err(OptimizeError::ConstraintsNotMet)));
+    assert!(matches!(optimize_weapons(stats, &[], &echoes, target, 90, Enemy::new(90), &options), Err(OptimizeError::NotEnoughWeapons)));
+}
+
+#[test]
+fn test_optimize_partial_builds_and_errors() {
+    let mut stats = Stats::new_from_base(BaseStats {hp: 10000.0, atk: 400.0, def: 1000.0});
+    let target = Target {
+        element: Element::Spectro,
+        skill_type: SkillType::Skill,
+        skill_multiplier: 1.0,
+        skill_scaling_bonus: 1.0,
+        scaling: Scaling::ATK,
+    };
+
+    // Partially farmed characters get the best build out of what they have
+    let echoes = [echo(4, StatType::AtkMult, 0.33), echo(3, StatType::AtkMult, 0.30)];
+    let build = optimize(stats.clone(), &echoes, target, 90, Enemy::new(90)).unwrap();
+    assert_eq!(build.len(), 2);
+
+    // Three 4-costs already hit the cost limit
+    let echoes = [echo(4, StatType::AtkMult, 0.33); 5];
+    let build = optimize(stats.clone(), &echoes, target, 90, Enemy::new(90)).unwrap();
+    assert_eq!(build.len(), 3);
+
+    assert_eq!(optimize(stats.clone(), &[], target, 90, Enemy::new(90)), Err(OptimizeError::NotEnoughEchoes));
+    assert_eq!(optimize_weapons(stats.clone(), &[], &echoes, target, 90, Enemy::new(90), &OptimizeOptions::default()).map(|_| ()), Err(OptimizeError::NotEnoughWeapons));
+
+    let echoes = [echo(13, StatType::AtkMult, 0.33)];
+    assert_eq!(optimize(stats.clone(), &echoes, target, 90, Enemy::new(90)), Err(OptimizeError::NoValidCombination));
+
+    stats.atk_flat = f64::NAN;
+    let echoes = [echo(4, StatType::AtkMult, 0.33)];
+    assert_eq!(optimize(stats, &echoes, target, 90, Enemy::new(90)), Err(OptimizeError::NonFiniteDamage));
+}
+
+// Small xorshift generator, so random inventories are the same on every run
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn pick<T: Copy>(&mut self, items: &[T]) -> T {
+        items[(self.next() % items.len() as u64) as usize]
+    }
+}
+
+fn random_inventory(seed: u64, size: usize) -> Vec<Echo> {
+    const SUB_STATS: [(StatType, [f64; 4]); 13] = [
+        (StatType::AtkFlat, [30.0, 40.0, 50.0, 60.0]),
+        (StatType::AtkMult, [0.064, 0.079, 0.094, 0.116]),
+        (StatType::HpFlat, [320.0, 390.0, 470.0, 580.0]),
+        (StatType::HpMult, [0.064, 0.079, 0.094, 0.116]),
+        (StatType::DefFlat, [40.0, 50.0, 60.0, 70.0]),
+        (StatType::DefMult, [0.081, 0.100, 0.118, 0.147]),
+        (StatType::CritRate, [0.063, 0.075, 0.087, 0.105]),
+        (StatType::CritDmg, [0.126, 0.150, 0.174, 0.210]),
+        (StatType::EnergyRegen, [0.068, 0.084, 0.100, 0.124]),
+        (StatType::SkillDmg, [0.064, 0.079, 0.094, 0.116]),
+        (StatType::BasicDmg, [0.064, 0.079, 0.094, 0.116]),
+        (StatType::HeavyDmg, [0.064, 0.079, 0.094, 0.116]),
+        (StatType::LiberationDmg, [0.064, 0.079, 0.094, 0.116]),
+    ];
+    const SONATAS: [Sonata; 5] = [Sonata::None, Sonata::SierraGale, Sonata::LingeringTunes, Sonata::MoltenRift, Sonata::RejuvenatingGlow];
+
+    let mut rng = Rng(seed);
+    (0..size)
+        .map(|_| {
+            let (cost, main_stats): (isize, &[(StatType, f64)]) = match rng.next() % 10 {
+                0..=1 => (4, &[(StatType::CritRate, 0.22), (StatType::CritDmg, 0.44), (StatType::AtkMult, 0.33), (StatType::HpMult, 0.33), (StatType::Healing, 0.264)]),
+                2..=4 => (3, &[(StatType::AtkMult, 0.30), (StatType::HpMult, 0.30), (StatType::EnergyRegen, 0.32), (StatType::AeroDmg, 0.30), (StatType::FusionDmg, 0.30)]),
+                _ => (1, &[(StatType::AtkMult, 0.18), (StatType::HpMult, 0.228), (StatType::DefMult, 0.18)]),
+            };
+            let (main_stat_type, main_stat_value) = rng.pick(main_stats);
+            let mut echo = echo(cost, main_stat_type, main_stat_value);
+            echo.sonata = rng.pick(&SONATAS);
+
+            let mut sub_stat_types = SUB_STATS.to_vec();
+            for sub_stat in echo.sub_stats.iter_mut() {
+                let (stat_type, tiers) = sub_stat_types.swap_remove((rng.next() % sub_stat_types.len() as u64) as usize);
+                *sub_stat = (stat_type, rng.pick(&tiers));
+            }
+            echo
+        })
+        .collect()
+}
+
+// Scores every single build that meets the constraints, with the same tie breaking as the optimizer
+fn exhaustive_search(stats: &Stats, echoes: &[Echo], score: &impl Fn(&Stats, &[Echo]) -> f64, constraints: &[Constraint]) -> Option<(f64, Vec<usize>)> {
+    // `score` returns `None` for builds that break a constraint
+    fn search(stats: &Stats, echoes: &[Echo], score: &impl Fn(&Stats, &[Echo]) -> Option<f64>, start: usize, cost: isize,
+              build: &mut Vec<usize>, best: &mut Option<(f64, Vec<usize>)>) {
+        for i in start..echoes.len() {
+            if cost + echoes[i].cost > MAX_COST {
+                continue;
+            }
+            build.push(i);
+            let mut build_stats = stats.clone();
+            for &index in build.iter() {
+                echoes[index].add_to_stats(&mut build_stats);
+            }
+            let build_echoes: Vec<Echo> = build.iter().map(|&index| echoes[index]).collect();
+            add_set_bonuses(&build_echoes, &mut build_stats, true);
+            if let Some(value) = score(&build_stats, &build_echoes) {
+                let is_better = match best {
+                    Some((best_value, best_build)) => value > *best_value
+                        || (value == *best_value && (build.len() > best_build.len() || (build.len() == best_build.len() && *build < *best_build))),
+                    None => true,
+                };
+                if is_better {
+                    *best = Some((value, build.clone()));
+                }
+            }
+            if build.len() < MAX_ECHOES {
+                search(stats, echoes, score, i + 1, cost + echoes[i].cost, build, best);
+            }
+            build.pop();
+        }
+    }
+
+    let mut best = None;
+    let allowed_score = |stats: &Stats, build: &[Echo]| constraints.iter()
+        .all(|constraint| constraint.allows(stats))
+        .then(|| score(stats, build));
+    search(stats, echoes, &allowed_score, 0, 0, &mut Vec::new(), &mut best);
+    best
+}
+
+#[test]
+fn test_optimize_matches_exhaustive_search() {
+    let stats = Stats::new_from_base(BaseStats {hp: 10000.0, atk: 400.0, def: 1000.0});
+    let target = Target {
+        element: Element::Aero,
+        skill_type: SkillType::Basic,
+        skill_multiplier: 1.0,
+        skill_scaling_bonus: 1.0,
+        scaling: Scaling::ATK,
+    };
+    let heal = HealTarget {
+        multiplier: 0.2,
+        flat: 500.0,
+        scaling: Scaling::HP,
+    };
+
+    for seed in 1..=3 {
+        let echoes = random_inventory(seed, 24);
+        let damage = |stats: &Stats| stats.skill_adjusted_damage_average(target, 90, Enemy::new(90));
+        let healing = |stats: &Stats| stats.heal_amount(heal);
+
+        let (value, indices) = exhaustive_search(&stats, &echoes, &|stats: &Stats, _: &[Echo]| damage(stats), &[]).unwrap();
+        let expected: Vec<Echo> = indices.iter().map(|&i| echoes[i]).collect();
+        assert_eq!(optimize_by(stats.clone(), &echoes, damage, None, &OptimizeOptions::default()).unwrap(), (value, expected));
+
+        let (value, indices) = exhaustive_search(&stats, &echoes, &|stats: &Stats, _: &[Echo]| healing(stats), &[]).unwrap();
+        let expected: Vec<Echo> = indices.iter().map(|&i| echoes[i]).collect();
+        assert_eq!(optimize_by(stats.clone(), &echoes, healing, None, &OptimizeOptions::default()).unwrap(), (value, expected));
+    }
+}
+
+#[test]
+fn test_optimize_echo_skills() {
+    let stats = Stats::new_from_base(BaseStats {hp: 10000.0, atk: 400.0, def: 1000.0});
+    let target = Target {
+        element: Element::Aero,
+        skill_type: SkillType::Basic,
+        skill_multiplier: 1.0,
+        skill_scaling_bonus: 1.0,
+        scaling: Scaling::ATK,
+    };
+    let skill = Target {
+        element: Element::Aero,
+        skill_type: SkillType::Skill,
+        skill_multiplier: 2.0,
+        skill_scaling_bonus: 1.0,
+        scaling: Scaling::ATK,
+    };
+    let enemy = Enemy::new(90);
+    let identity = |id, skill| EchoIdentity {id, class: EchoClass::Overlord, skill};
+
+    // Only three 4-cost echoes fit, and the one with a skill has the worst main stat
+    let mut echoes = vec![echo(4, StatType::AtkMult, 0.33); 3];
+    echoes.push(Echo { identity: Some(identity(1, Some(skill))), ..echo(4, StatType::AtkMult, 0.20) });
+    let build = optimize(stats.clone(), &echoes, target, 90, enemy).unwrap();
+    assert_eq!(build[0], echoes[3]);
+    assert_eq!(build[1..], echoes[..2]);
+    let options = OptimizeOptions {echo_skill_uses: 0.0, ..Default::default()};
+    let build = optimize_with(stats.clone(), &echoes, target, 90, enemy, &options).unwrap();
+    assert_eq!(build, echoes[..3]);
+    for echo_skill_uses in [-1.0, f64::NAN, f64::INFINITY] {
+        let options = OptimizeOptions {echo_skill_uses, ..Default::default()};
+        assert_eq!(optimize_with(stats.clone(), &echoes, target, 90, enemy, &options), Err(OptimizeError::InvalidWeight));
+    }
+
+    // Copies of the same monster don't stack set bonuses, and builds are scored with the best skill they have
+    for seed in 1..=3 {
+        let mut rng = Rng(seed);
+        let echoes: Vec<Echo> = random_inventory(seed, 24).into_iter()
+            .map(|echo| {
+                let skill = Target {skill_multiplier: 0.5 + (rng.next() % 4) as f64, ..skill};
+                let identity = identity(rng.pick(&[1, 2, 3, 4]), (echo.cost == 4).then_some(skill));
+                Echo { identity: Some(identity), ..echo }
+            })
+            .collect();
+        let damage = |stats: &Stats, build: &[Echo]| {
+            let skill_damage = build.iter()
+                .filter_map(Echo::skill)
+                .map(|skill| stats.skill_adjusted_damage_average(skill, 90, enemy))
+                .fold(0.0, f64::max);
+            stats.skill_adjusted_damage_average(target, 90, enemy) + skill_damage
+        };
+
+        let (value, indices) = exhaustive_search(&stats, &echoes, &damage, &[]).unwrap();
+        let best = optimize_top(stats.clone(), &echoes, target, 90, enemy, 1, &OptimizeOptions::default()).unwrap().remove(0);
+        assert!((best.damage - value).abs() <= value * 1e-12);
+        let main = indices.iter().copied()
+            .filter(|&i| echoes[i].skill().is_some())
+            .reduce(|main, i| if echoes[i].skill().unwrap().skill_multiplier > echoes[main].skill().unwrap().skill_multiplier { i } else { main });
+        let mut expected: Vec<Echo> = indices.iter().filter(|&&i| Some(i) != main).map(|&i| echoes[i]).collect();
+        expected.splice(0..0, main.map(|main| echoes[main]));
+        assert_eq!(best.echoes, expected);
+    }
+}
+
+#[test]
+fn test_optimize_constraints() {
+    let stats = Stats::new_from_base(BaseStats {hp: 10000.0, atk: 400.0, def: 1000.0});
+    let target = Target {
+        element: Element::Aero,
+        skill_type: SkillType::Basic,
+        skill_multiplier: 1.0,
+        skill_scaling_bonus: 1.0,
+        scaling: Scaling::ATK,
+    };
+    let damage = |stats: &Stats| stats.skill_adjusted_damage_average(target, 90, Enemy::new(90));
+    // Constraints can capture values, like an Energy Regen target worked out from the energy a rotation needs
+    let energy_needed = 140.0;
+    let constraint_sets = [
+        vec![Constraint::at_least(|stats| stats.energy_regen, 1.3)],
+        vec![Constraint::at_most(|stats| stats.crit_rate, 0.3)],
+        vec![Constraint::at_least(|stats| stats.energy_regen, 1.15), Constraint::between(Stats::hp, 12000.0, 17000.0)],
+        vec![Constraint::at_least(move |stats| stats.energy_regen * 100.0 - energy_needed, 0.0)],
+    ];
+
+    for seed in 1..=3 {
+        let echoes = random_inventory(seed, 24);
+        let (_, unconstrained) = optimize_by(stats.clone(), &echoes, damage, None, &OptimizeOptions::default()).unwrap();
+
+        for constraints in &constraint_sets {
+            let (value, indices) = exhaustive_search(&stats, &echoes, &|stats: &Stats, _: &[Echo]| damage(stats), constraints).unwrap();
+            let expected: Vec<Echo> = indices.iter().map(|&i| echoes[i]).collect();
+            assert_ne!(expected, unconstrained);
+
+            let options = OptimizeOptions {constraints: constraints.clone(), ..Default::default()};
+            assert_eq!(optimize_by(stats.clone(), &echoes, damage, None, &options).unwrap(), (value, expected));
+        }
+    }
+
+    let echoes = inventory();
+    let options = OptimizeOptions {constraints: vec![Constraint::at_least(|stats| stats.energy_regen, 10.0)], ..Default::default()};
+    assert_eq!(optimize_with(stats, &echoes, target, 90, Enemy::new(90), &options), Err(OptimizeError::ConstraintsNotMet));
+}
+
+// Scores every build, or only the ones that can't fit another echo if `maximal`, ranked with the same tie breaking
+// as the optimizer
+fn ranked_builds(stats: &Stats, echoes: &[Echo], score: &impl Fn(&Stats) -> f64, maximal: bool) -> Vec<(f64, Vec<usize>)> {
+    let mut builds = Vec::new();
+    for mask in 1u32..(1 << echoes.len()) {
+        let build: Vec<usize> = (0..echoes.len()).filter(|i| mask & (1 << i) != 0).collect();
+        let cost: isize = build.iter().map(|&i| echoes[i].cost).sum();
+        let fits_another = build.len() < MAX_ECHOES
+            && (0..echoes.len()).any(|i| mask & (1 << i) == 0 && cost + echoes[i].cost <= MAX_COST);
+        if build.len() > MAX_ECHOES || cost > MAX_COST || (maximal && fits_another) {
+            continue;
+        }
+
+        let mut build_stats = stats.clone();
+        for &index in &build {
+            echoes[index].add_to_stats(&mut build_stats);
+        }
+        let build_echoes: Vec<Echo> = build.iter().map(|&index| echoes[index]).collect();
+        add_set_bonuses(&build_echoes, &mut build_stats, true);
+        builds.push((score(&build_stats), build));
+    }
+    builds.sort_by(|(a_value, a), (b_value, b)| b_value.total_cmp(a_value).then(b.len().cmp(&a.len())).then(a.cmp(b)));
+    builds
+}
+
+#[test]
+fn test_optimize_top() {
+    let stats = Stats::new_from_base(BaseStats {hp: 10000.0, atk: 400.0, def: 1000.0});
+    let target = Target {
+        element: Element::Aero,
+        skill_type: SkillType::Basic,
+        skill_multiplier: 1.0,
+        skill_scaling_bonus: 1.0,
+        scaling: Scaling::ATK,
+    };
+    let damage = |stats: &Stats| stats.skill_adjusted_damage_average(target, 90, Enemy::new(90));
+
+    for seed in 7..=9 {
+        let echoes = random_inventory(seed, 14);
+        let expected = ranked_builds(&stats, &echoes, &damage, true);
+
+        for threads in [1, 3] {
+            let builds = optimize_top(stats.clone(), &echoes, target, 90, Enemy::new(90), 10, &OptimizeOptions {threads, ..Default::default()}).unwrap();
+            assert_eq!(builds.len(), 10);
+            for (build, (value, indices)) in builds.iter().zip(&expected) {
+                assert_eq!(build.damage, *value);
+                assert_eq!(build.echoes, indices.iter().map(|&i| echoes[i]).collect::<Vec<Echo>>());
+                assert_eq!(build.cost, build.echoes.iter().map(|echo| echo.cost).sum::<isize>());
+                assert_eq!(damage(&build.stats), build.damage);
+            }
+        }
+    }
+
+    // Asking for more builds than there are returns all of them
+    let echoes = vec![echo(4, StatType::AtkMult, 0.33), echo(4, StatType::CritRate, 0.22)];
+    let builds = optimize_top(stats.clone(), &echoes, target, 90, Enemy::new(90), 5, &OptimizeOptions::default()).unwrap();
+    assert_eq!(builds.len(), 1);
+    assert_eq!(builds[0].cost, 8);
+    assert!(optimize_top(stats, &echoes, target, 90, Enemy::new(90), 0, &OptimizeOptions::default()).unwrap().is_empty());
+}
+
+#[test]
+fn test_optimize_locked_and_excluded() {
+    let stats = Stats::new_from_base(BaseStats {hp: 10000.0, atk: 400.0, def: 1000.0});
+    let target = Target {
+        element: Element::Aero,
+        skill_type: SkillType::Basic,
+        skill_multiplier: 1.0,
+        skill_scaling_bonus: 1.0,
+        scaling: Scaling::ATK,
+    };
+    let damage = |stats: &Stats| stats.skill_adjusted_damage_average(target, 90, Enemy::new(90));
+
+    for seed in 10..=12 {
+        let echoes = random_inventory(seed, 14);
+        for (locked, excluded) in [(vec![3], vec![0, 5]), (vec![9, 1], vec![]), (vec![], vec![2, 4, 6])] {
+            let mut expected: Option<(f64, Vec<usize>)> = None;
+            for (value, build) in ranked_builds(&stats, &echoes, &damage, false) {
+                let allowed = locked.iter().all(|index| build.contains(index)) && !excluded.iter().any(|index| build.contains(index));
+                if allowed {
+                    expected = Some((value, build));
+                    break;
+                }
+            }
+            let (value, indices) = expected.unwrap();
+
+            let options = OptimizeOptions {locked: locked.clone(), excluded, ..Default::default()};
+            assert_eq!(optimize_by(stats.clone(), &echoes, damage, None, &options).unwrap(), (value, indices.iter().map(|&i| echoes[i]).collect()));
+        }
+    }
+
+    let echoes = inventory();
+    let build = |locked: Vec<usize>, excluded: Vec<usize>| {
+        let options = OptimizeOptions {locked, excluded, ..Default::default()};
+        optimize_with(stats.clone(), &echoes, target, 90, Enemy::new(90), &options)
+    };
+    assert_eq!(build(vec![9, 7, 8, 6, 5], vec![]).unwrap(), echoes[5..].to_vec());
+    assert_eq!(build(vec![], (0..10).collect()), Err(OptimizeError::NoValidCombination));
+    assert_eq!(build(vec![10], vec![]), Err(OptimizeError::InvalidLocks));
+    assert_eq!(build(vec![1], vec![1]), Err(OptimizeError::InvalidLocks));
+    assert_eq!(build(vec![1, 1], vec![]), Err(OptimizeError::InvalidLocks));
+    assert_eq!(build(vec![0, 1, 2, 3], vec![]), Err(OptimizeError::InvalidLocks));
+    assert_eq!(build(vec![5, 6, 7, 8, 9, 4], vec![]), Err(OptimizeError::InvalidLocks));
+}
+
+// The team's damage when every member is optimized one after the other, out of the echoes the ones before it left
+fn sequential_team_damage(members: &[TeamMember], echoes: &[Echo]) -> f64 {
+    let mut excluded = Vec::new();
+    let mut damage = 0.0;
+    for member in members {
+        let options = OptimizeOptions {excluded: excluded.clone(), ..Default::default()};
+        let build = optimize_rotation(member.stats.clone(), echoes, &member.rotation, 90, Enemy::new(90), &options).unwrap();
+        let mut stats = member.stats.clone();
+        for echo in &build {
+            echo.add_to_stats(&mut stats);
+            excluded.push(echoes.iter().enumerate().position(|(i, other)| other == echo && !excluded.contains(&i)).unwrap());
+        }
+        add_set_bonuses(&build, &mut stats, true);
+        damage += member.weight * Objective::Average.rotation_score(&stats, &member.rotation, 90, Enemy::new(90));
+    }
+    damage
+}
+
+fn assert_disjoint(builds: &[Build], echoes: &[Echo]) {
+    for echo in echoes {
+        let assigned = builds.iter().flat_map(|build| &build.echoes).filter(|assigned| *assigned == echo).count();
+        assert!(assigned <= echoes.iter().filter(|other| *other == echo).count());
+    }
+}
+
+#[test]
+fn test_optimize_team() {
+    let stats = Stats::new_from_base(BaseStats {hp: 10000.0, atk: 400.0, def: 1000.0});
+    let target = Target {
+        element: Element::Aero,
+        skill_type: SkillType::Basic,
+        skill_multiplier: 1.0,
+        skill_scaling_bonus: 1.0,
+        scaling: Scaling::ATK,
+    };
+    let member = |rotation: Vec<(Target, f64)>, weight: f64| TeamMember {
+        stats: stats.clone(),
+        rotation,
+        character_level: 90,
+        weight,
+        options: OptimizeOptions::default(),
+    };
+
+    // The first member only gets a little more out of the best echo than out of the others, while the second one
+    // hits a lot harder and gets a lot more out of it, even though it has the same weight
+    let mut best = echo(4, StatType::AtkMult, 0.33);
+    best.sub_stats[0] = (StatType::AtkMult, 0.116);
+    let plain = echo(4, StatType::AtkMult, 0.33);
+    let echoes = [best, plain, plain, plain, plain, plain];
+    let members = [member(vec![(target, 1.0)], 1.0), member(vec![(target, 3.0)], 1.0)];
+    let builds = optimize_team(&members, &echoes, Enemy::new(90)).unwrap();
+    assert_eq!(builds[0].echoes, vec![plain; 3]);
+    assert_eq!(builds[1].echoes, vec![best, plain, plain]);
+    assert_eq!(builds[1].damage, Objective::Average.rotation_score(&builds[1].stats, &members[1].rotation, 90, Enemy::new(90)));
+
+    // The builds never share echoes, and beat optimizing the members one after the other
+    let heavy = Target {skill_type: SkillType::Heavy, ..target};
+    let liberation = Target {skill_type: SkillType::Liberation, scaling: Scaling::HP, ..target};
+    let members = [
+        member(vec![(target, 2.0), (heavy, 1.0)], 2.0),
+        member(vec![(heavy, 3.0)], 1.0),
+        member(vec![(liberation, 1.0)], 1.0),
+    ];
+    for seed in 13..=14 {
+        let echoes = random_inventory(seed, 24);
+        let builds = optimize_team(&members, &echoes, Enemy::new(90)).unwrap();
+        assert_disjoint(&builds, &echoes);
+        let team = members.iter().zip(&builds).map(|(member, build)| member.weight * build.damage).sum::<f64>();
+        assert!(team >= sequential_team_damage(&members, &echoes));
+    }
+
+    let mut members = [member(vec![(target, 1.0)], 1.0), member(vec![(target, 1.0)], 1.0)];
+    members[1].options.locked = vec![0];
+    let builds = optimize_team(&members, &echoes, Enemy::new(90)).unwrap();
+    assert!(builds[1].echoes.contains(&best));
+    members[0].options.locked = vec![0];
+    assert_eq!(optimize_team(&members, &echoes, Enemy::new(90)).err(), Some(OptimizeError::InvalidLocks));
+
+    let members = [member(vec![(target, 1.0), (target, -0.5)], 1.0), member(vec![(target, 1.0)], 1.0)];
+    assert_eq!(optimize_team(&members, &echoes, Enemy::new(90)).err(), Some(OptimizeError::InvalidWeight));
+    let members = [member(vec![(target, 1.0)], -1.0), member(vec![(target, 1.0)], 1.0)];
+    assert_eq!(optimize_team(&members, &echoes, Enemy::new(90)).err(), Some(OptimizeError::InvalidWeight));
+}
+
+#[test]
+#[ignore] // Slow in debug builds, run with `cargo test --release -- --ignored`
+fn test_optimize_team_large_inventory() {
+    let stats = Stats::new_from_base(BaseStats {hp: 10000.0, atk: 400.0, def: 1000.0});
+    let target = Target {
+        element: Element::Aero,
+        skill_type: SkillType::Basic,
+        skill_multiplier: 1.0,
+        skill_scaling_bonus: 1.0,
+        scaling: Scaling::ATK,
+    };
+    let heavy = Target {skill_type: SkillType::Heavy, ..target};
+    let liberation = Target {skill_type: SkillType::Liberation, scaling: Scaling::HP, ..target};
+    let members = [(vec![(target, 2.0), (heavy, 1.0)], 2.0), (vec![(heavy, 3.0)], 1.0), (vec![(liberation, 1.0)], 1.0)]
+        .map(|(rotation, weight)| TeamMember {stats: stats.clone(), rotation, character_level: 90, weight, options: OptimizeOptions::parallel()});
+
+    // An inventory about as large as a player's, where every member competes for the same echoes
+    let echoes = random_inventory(42, 300);
+    let builds = optimize_team(&members, &echoes, Enemy::new(90)).unwrap();
+    assert!(builds.iter().all(|build| build.echoes.len() == MAX_ECHOES && build.cost <= MAX_COST));
+    assert_disjoint(&builds, &echoes);
+    let team = members.iter().zip(&builds).map(|(member, build)| member.weight * build.damage).sum::<f64>();
+    assert!(team >= sequential_team_damage(&members, &echoes));
+}
+
+#[test]
+fn test_optimize_parallel_matches_serial() {
+    let stats = Stats::new_from_base(BaseStats {hp: 10000.0, atk: 400.0, def: 1000.0});
+    let target = Target {
+        element: Element::Aero,
+        skill_type: SkillType::Basic,
+        skill_multiplier: 1.0,
+        skill_scaling_bonus: 1.0,
+        scaling: Scaling::ATK,
+    };
+
+    for seed in 4..=6 {
+        // Every echo appears twice, so there are many builds tied for the best
+        let mut echoes = random_inventory(seed, 16);
+        echoes.extend(echoes.clone());
+
+        let serial = optimize(stats.clone(), &echoes, target, 90, Enemy::new(90)).unwrap();
+        for threads in [2, 3, 8] {
+            let options = OptimizeOptions {threads, ..Default::default()};
+            assert_eq!(optimize_with(stats.clone(), &echoes, target, 90, Enemy::new(90), &options).unwrap(), serial);
+        }
+    }
+}
+
+#[test]
+#[ignore] // Slow in debug builds, run with `cargo test --release -- --ignored`
+fn test_optimize_large_inventory() {
+    let stats = Stats::new_from_base(BaseStats {hp: 10000.0, atk: 400.0, def: 1000.0});
+    let target = Target {
+        element: Element::Aero,
+        skill_type: SkillType::Basic,
+        skill_multiplier: 1.0,
+        skill_scaling_bonus: 1.0,
+        scaling: Scaling::ATK,
+    };
+    let echoes = random_inventory(42, 500);
+    let enemy = Enemy::new(90);
+
+    // An exhaustive search isn't feasible, but no build that swaps a single echo can beat the best one, and threads
+    // don't change it. How long it takes is measured by `cargo bench` instead
+    let build = optimize(stats.clone(), &echoes, target, 90, enemy).unwrap();
+    assert_eq!(build.len(), MAX_ECHOES);
+    assert!(build.iter().map(|echo| echo.cost).sum::<isize>() <= MAX_COST);
+    assert_eq!(optimize_with(stats.clone(), &echoes, target, 90, enemy, &OptimizeOptions::parallel()).unwrap(), build);
+
+    let damage = |build: &[Echo]| {
+        let mut stats = stats.clone();
+        for echo in build {
+            echo.add_to_stats(&mut stats);
+        }
+        add_set_bonuses(build, &mut stats, true);
+        stats.skill_adjusted_damage_average(target, 90, enemy)
+    };
+    let best = damage(&build);
+    for i in 0..build.len() {
+        for echo in &echoes {
+            let mut swapped = build.clone();
+            swapped[i] = *echo;
+            if swapped.iter().map(|echo| echo.cost).sum::<isize>() <= MAX_COST && !build.contains(echo) {
+                assert!(damage(&swapped) <= best * (1.0 + 1e-12));
+            }
+        }
+    }
+}
@@ -0,0 +1,195 @@
+use crate::calculator::{Enemy, Stats, Target};
+use crate::echo::{Echo, StatType, SUB_STAT_TIERS};
+use crate::modifier::StatModifier;
+
+#[cfg(test)]
+mod tests;
+
+// Amount of each stat added to the baseline to measure its weight. Flat stats are in the hundreds or thousands, so
+// they're measured one point at a time
+const PERCENT_STEP: f64 = 1e-4;
+const FLAT_STEP: f64 = 1.0;
+
+/// The `StatWeights` struct holds how much each stat is worth to a character: the fraction of its damage gained for
+/// every point of the stat added to a baseline. Stats that don't increase its damage are worth 0
+#[derive(Clone, PartialEq, Debug)]
+pub struct StatWeights {
+    weights: [f64; StatType::ALL.len()],
+}
+
+/// The `EchoScore` struct rates an echo with a set of `StatWeights`. Contributions are the fraction of damage a stat
+/// of the echo adds, as estimated by the weights
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct EchoScore {
+    /// Contribution of the substats, relative to the best substats any echo could roll, from 0 to 1. Main stats are
+    /// left out, since they're the same for every echo of a cost and main stat type
+    pub score: f64,
+    /// Contribution of the main and secondary stats together
+    pub main_stats: f64,
+    /// Contribution of each substat, in the order of `Echo::sub_stats`
+    pub sub_stats: [f64; 5],
+}
+
+/// The `StatPriority` struct represents how much damage one typical roll of a stat adds, as given by
+/// `StatType::typical_roll`
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct StatPriority {
+    pub stat_type: StatType,
+    pub roll: f64,
+    pub damage_gain: f64,
+    /// Damage gain as a fraction of the baseline damage
+    pub relative_gain: f64,
+}
+
+impl EchoScore {
+    /// Returns the contribution of every stat of the echo, for comparing echoes of different main stats
+    pub fn total(&self) -> f64 {
+        self.main_stats + self.sub_stats.iter().sum::<f64>()
+    }
+}
+
+impl StatWeights {
+    /// Derives the weight of each stat from `damage`, by measuring how much it grows when a little of the stat is
+    /// added to `stats`. `stats` should be the ones of a character with a typical build, since the weights of stats
+    /// change as they grow
+    pub fn new(stats: &Stats, damage: impl Fn(&Stats) -> f64) -> StatWeights {
+        let baseline = damage(stats);
+        let mut weights = [0.0; StatType::ALL.len()];
+        if baseline <= 0.0 {
+            return StatWeights {weights};
+        }
+
+        let mut stats = stats.clone();
+        for (weight, stat_type) in weights.iter_mut().zip(StatType::ALL) {
+            let step = if stat_type.is_flat() { FLAT_STEP } else { PERCENT_STEP };
+            stat_type.add_to_stats(&mut stats, step);
+            *weight = ((damage(&stats) - baseline) / baseline / step).max(0.0);
+            stat_type.remove_from_stats(&mut stats, step);
+        }
+        StatWeights {weights}
+    }
+
+    /// Derives the weight of each stat from the average damage of a target, like `StatWeights::new`
+    ///
+    /// # Examples
+    /// ```
+    /// use wuwa_calculator::{Element, SkillType};
+    /// use wuwa_calculator::calculator::{BaseStats, Enemy, Scaling, Stats, Target};
+    /// use wuwa_calculator::echo::{Echo, StatType};
+    /// use wuwa_calculator::scoring::StatWeights;
+    /// use wuwa_calculator::sonata::Sonata;
+    ///
+    /// let stats = Stats::new_from_base(BaseStats {hp: 10000.0, atk: 400.0, def: 1000.0});
+    /// let target = Target {
+    ///     element: Element::Aero,
+    ///     skill_type: SkillType::Basic,
+    ///     skill_multiplier: 1.0,
+    ///     skill_scaling_bonus: 1.0,
+    ///     scaling: Scaling::ATK,
+    /// };
+    /// let weights = StatWeights::for_target(&stats, target, 90, Enemy::new(90));
+    /// assert_eq!(weights.weight(StatType::HpMult), 0.0);
+    ///
+    /// let sub_stats = [
+    ///     (StatType::CritRate, 0.105),
+    ///     (StatType::BasicDmg, 0.116),
+    ///     (StatType::HpFlat, 580.0),
+    ///     (StatType::None, 0.0),
+    ///     (StatType::None, 0.0),
+    /// ];
+    /// let echo = Echo::new(4, 5, Sonata::SierraGale, None, StatType::CritDmg, 25, sub_stats).unwrap();
+    /// let score = weights.score(&echo);
+    /// assert!(score.sub_stats[0] > 0.0 && score.sub_stats[2] == 0.0);
+    /// assert!(score.score > 0.0 && score.score < 1.0);
+    /// ```
+    pub fn for_target(stats: &Stats, target: Target, character_level: isize, enemy: Enemy) -> StatWeights {
+        StatWeights::new(stats, |stats| stats.skill_adjusted_damage_average(target, character_level, enemy))
+    }
+
+    /// Returns the weight of a stat type
+    pub fn weight(&self, stat_type: StatType) -> f64 {
+        match stat_type {
+            StatType::None => 0.0,
+            _ => self.weights[stat_type as usize],
+        }
+    }
+
+    /// Estimates the fraction of damage an amount of a stat adds
+    pub fn contribution(&self, stat_type: StatType, value: f64) -> f64 {
+        self.weight(stat_type) * value
+    }
+
+    /// Rates an echo, so an inventory can be sorted or pruned without running the optimizer
+    pub fn score(&self, echo: &Echo) -> EchoScore {
+        let sub_stats = echo.sub_stats.map(|(stat_type, value)| self.contribution(stat_type, value));
+        let best = self.best_sub_stats();
+        EchoScore {
+            score: if best > 0.0 { sub_stats.iter().sum::<f64>() / best } else { 0.0 },
+            main_stats: self.contribution(echo.main_stat_type, echo.main_stat_value)
+                + self.contribution(echo.secondary_stat_type, echo.secondary_stat_value),
+            sub_stats,
+        }
+    }
+
+    // Contribution of the best substats an echo can have: the most valuable stats, each at its highest tier
+    fn best_sub_stats(&self) -> f64 {
+        let mut best: Vec<f64> = SUB_STAT_TIERS.iter()
+            .map(|tiers| self.contribution(tiers.stat_type, tiers.values[tiers.values.len() - 1]))
+            .collect();
+        best.sort_by(|a, b| b.total_cmp(a));
+        best.iter().take(5).sum()
+    }
+}
+
+/// Measures the damage one typical roll of each stat echoes can have adds to `stats`, by applying the roll and
+/// taking it back, and sorts the stats from the one that adds the most to the one that adds the least. Stats that add
+/// the same come in `StatType::ALL` order. Stats that are only main stats, like elemental DMG bonuses, are measured
+/// with a main stat's worth of them, so they rank above substats of similar weight
+pub fn stat_priorities(stats: &Stats, damage: impl Fn(&Stats) -> f64) -> Vec<StatPriority> {
+    let baseline = damage(stats);
+    let mut stats = stats.clone();
+    let mut priorities: Vec<StatPriority> = StatType::ALL.iter()
+        .filter_map(|&stat_type| {
+            let roll = stat_type.typical_roll()?;
+            let modifier = StatModifier::add(stat_type, roll);
+            modifier.apply(&mut stats);
+            let damage_gain = damage(&stats) - baseline;
+            modifier.revert(&mut stats);
+            Some(StatPriority {
+                stat_type,
+                roll,
+                damage_gain,
+                relative_gain: if baseline > 0.0 { damage_gain / baseline } else { 0.0 },
+            })
+        })
+        .collect();
+    priorities.sort_by(|a, b| b.damage_gain.total_cmp(&a.damage_gain));
+    priorities
+}
+
+/// Same as `stat_priorities`, for the average damage of a target
+///
+/// # Examples
+/// ```
+/// use wuwa_calculator::{Element, SkillType};
+/// use wuwa_calculator::calculator::{BaseStats, Enemy, Scaling, Stats, Target};
+/// use wuwa_calculator::echo::StatType;
+/// use wuwa_calculator::scoring::stat_priorities_for_target;
+///
+/// let stats = Stats::new_from_base(BaseStats {hp: 10000.0, atk: 400.0, def: 1000.0});
+/// let target = Target {
+///     element: Element::Aero,
+///     skill_type: SkillType::Liberation,
+///     skill_multiplier: 1.0,
+///     skill_scaling_bonus: 1.0,
+///     scaling: Scaling::ATK,
+/// };
+/// let priorities = stat_priorities_for_target(&stats, target, 90, Enemy::new(90));
+/// let rank = |stat_type| priorities.iter().position(|priority| priority.stat_type == stat_type).unwrap();
+/// assert!(rank(StatType::CritRate) < rank(StatType::HpMult));
+/// assert!(rank(StatType::AeroDmg) < rank(StatType::FusionDmg));
+/// assert!(rank(StatType::LiberationDmg) < rank(StatType::BasicDmg));
+/// ```
+pub fn stat_priorities_for_target(stats: &Stats, target: Target, character_level: isize, enemy: Enemy) -> Vec<StatPriority> {
+    stat_priorities(stats, |stats| stats.skill_adjusted_damage_average(target, character_level, enemy))
+}
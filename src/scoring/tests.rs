@@ -0,0 +1,97 @@
+use crate::calculator::{BaseStats, Enemy, Scaling, Stats, Target};
+use crate::{Element, SkillType};
+use crate::echo::{Echo, StatType, SUB_STAT_TIERS};
+use crate::scoring::{stat_priorities_for_target, StatWeights};
+use crate::sonata::Sonata;
+
+#[test]
+fn test_echo_score() {
+    let mut stats = Stats::new_from_base(BaseStats {hp: 10000.0, atk: 400.0, def: 1000.0});
+    StatType::CritRate.add_to_stats(&mut stats, 0.3);
+    let target = Target {
+        element: Element::Havoc,
+        skill_type: SkillType::Heavy,
+        skill_multiplier: 1.0,
+        skill_scaling_bonus: 1.0,
+        scaling: Scaling::HP,
+    };
+    let enemy = Enemy::new(90);
+    let damage = |stats: &Stats| stats.skill_adjusted_damage_average(target, 90, enemy);
+    let weights = StatWeights::for_target(&stats, target, 90, enemy);
+    assert_eq!(weights.weight(StatType::AtkMult), 0.0);
+    assert_eq!(weights.weight(StatType::BasicDmg), 0.0);
+    assert_eq!(weights.weight(StatType::None), 0.0);
+    assert!(weights.weight(StatType::HeavyDmg) > 0.0);
+
+    // Contributions estimate the damage gained from a single stat
+    for (stat_type, value) in [(StatType::HpMult, 0.116), (StatType::CritDmg, 0.21), (StatType::HpFlat, 580.0)] {
+        let mut with_stat = stats.clone();
+        stat_type.add_to_stats(&mut with_stat, value);
+        let gain = damage(&with_stat) / damage(&stats) - 1.0;
+        assert!((weights.contribution(stat_type, value) - gain).abs() < gain * 0.01);
+    }
+
+    let perfect = Echo::new(4, 5, Sonata::None, None, StatType::HpMult, 25, [
+        (StatType::CritRate, 0.105),
+        (StatType::CritDmg, 0.21),
+        (StatType::HpMult, 0.116),
+        (StatType::HeavyDmg, 0.116),
+        (StatType::HpFlat, 580.0),
+    ]).unwrap();
+    let score = weights.score(&perfect);
+    assert!((score.score - 1.0).abs() < 1e-12);
+    assert!((score.total() - score.main_stats - score.sub_stats.iter().sum::<f64>()).abs() < 1e-12);
+
+    let mut worse = perfect;
+    worse.sub_stats[4] = (StatType::AtkFlat, 60.0);
+    assert_eq!(weights.score(&worse).sub_stats[4], 0.0);
+    assert!(weights.score(&worse).score < score.score);
+    let atk_main = Echo::new(4, 5, Sonata::None, None, StatType::AtkMult, 25, perfect.sub_stats).unwrap();
+    assert_eq!(weights.score(&atk_main).score, score.score);
+    assert!(weights.score(&atk_main).total() < score.total());
+
+    // Nothing is worth anything to a build that deals no damage
+    let weights = StatWeights::new(&stats, |_| 0.0);
+    assert_eq!(weights.score(&perfect).score, 0.0);
+}
+
+#[test]
+fn test_stat_priorities() {
+    let stats = Stats::new_from_base(BaseStats {hp: 10000.0, atk: 400.0, def: 1000.0});
+    let target = Target {
+        element: Element::Glacio,
+        skill_type: SkillType::Skill,
+        skill_multiplier: 1.0,
+        skill_scaling_bonus: 1.0,
+        scaling: Scaling::DEF,
+    };
+    let enemy = Enemy::new(90);
+    let priorities = stat_priorities_for_target(&stats, target, 90, enemy);
+
+    // Every stat echoes can have is ranked, from the most damage to the least
+    assert!(priorities.iter().all(|priority| priority.stat_type.typical_roll().is_some()));
+    assert!(priorities.len() > SUB_STAT_TIERS.len());
+    assert!(priorities.windows(2).all(|pair| pair[0].damage_gain >= pair[1].damage_gain));
+    let rank = |stat_type| priorities.iter().position(|priority| priority.stat_type == stat_type).unwrap();
+    assert!(rank(StatType::DefMult) < rank(StatType::AtkMult));
+    assert!(rank(StatType::SkillDmg) < rank(StatType::BasicDmg));
+
+    let crit_rate = priorities[rank(StatType::CritRate)];
+    assert!((crit_rate.roll - 0.084).abs() < 1e-12);
+    let mut with_roll = stats.clone();
+    StatType::CritRate.add_to_stats(&mut with_roll, crit_rate.roll);
+    let baseline = stats.skill_adjusted_damage_average(target, 90, enemy);
+    let gain = with_roll.skill_adjusted_damage_average(target, 90, enemy) - baseline;
+    assert!((crit_rate.damage_gain - gain).abs() < gain * 1e-12);
+    assert!((crit_rate.relative_gain - gain / baseline).abs() < 1e-12 * gain / baseline);
+    assert_eq!(priorities[rank(StatType::EnergyRegen)].damage_gain, 0.0);
+
+    // Elemental DMG bonuses are measured with a main stat's worth, and only the target's element adds damage
+    let glacio = priorities[rank(StatType::GlacioDmg)];
+    assert_eq!(glacio.roll, 0.30);
+    let mut with_roll = stats.clone();
+    StatType::GlacioDmg.add_to_stats(&mut with_roll, glacio.roll);
+    assert_eq!(glacio.damage_gain, with_roll.skill_adjusted_damage_average(target, 90, enemy) - baseline);
+    assert!(rank(StatType::GlacioDmg) < rank(StatType::BasicDmg));
+    assert_eq!(priorities[rank(StatType::FusionDmg)].damage_gain, 0.0);
+}
@@ -0,0 +1,146 @@
+use crate::calculator::Stats;
+use crate::echo::{max_echo_level, Echo, EchoError, StatType, SUB_STAT_TIERS};
+use crate::sonata::add_set_bonuses;
+
+#[cfg(test)]
+mod tests;
+
+/// Number of levels an echo needs to gain to unlock each of its substats
+pub const LEVELS_PER_SUB_STAT: isize = 5;
+
+/// The `DamageDistribution` struct holds the damage of a build in every outcome of a simulation
+#[derive(Clone, PartialEq, Debug)]
+pub struct DamageDistribution {
+    /// Damage of the build before the simulation, like with the echo at its current level
+    pub current: f64,
+    /// Damage of every simulated outcome, from lowest to highest
+    pub samples: Vec<f64>,
+}
+
+impl DamageDistribution {
+    /// Returns the average damage of the outcomes
+    pub fn mean(&self) -> f64 {
+        self.samples.iter().sum::<f64>() / self.samples.len() as f64
+    }
+
+    /// Returns how much damage is gained on average over the current damage
+    pub fn expected_gain(&self) -> f64 {
+        self.mean() - self.current
+    }
+
+    /// Returns the damage that a fraction `p` of the outcomes, between 0 and 1, don't go over. `percentile(0.5)` is
+    /// the median
+    pub fn percentile(&self, p: f64) -> f64 {
+        let rank = (p.clamp(0.0, 1.0) * (self.samples.len() - 1) as f64).round() as usize;
+        self.samples[rank]
+    }
+
+    /// Returns the fraction of the outcomes that deal at least `damage`
+    pub fn chance_of_at_least(&self, damage: f64) -> f64 {
+        let below = self.samples.partition_point(|sample| *sample < damage);
+        (self.samples.len() - below) as f64 / self.samples.len() as f64
+    }
+}
+
+/// Simulates leveling `echo` up to `target_level` `trials` times, and returns the distribution of the damage of the
+/// build it's part of, along with `others`, as scored by `damage` on the final stats with set bonuses.
+///
+/// The main and secondary stats grow to their values at the target level, and a substat is unlocked every
+/// `LEVELS_PER_SUB_STAT` levels. Each one is a stat the echo doesn't have yet, with every stat and every one of its
+/// tiers assumed to be equally likely. The same `seed` always gives the same outcomes.
+///
+/// Returns an error if the echo isn't valid, or if the target level is below its level or above the maximum level of
+/// its rarity.
+///
+/// # Examples
+/// ```
+/// use wuwa_calculator::{Element, SkillType};
+/// use wuwa_calculator::calculator::{BaseStats, Enemy, Scaling, Stats, Target};
+/// use wuwa_calculator::echo::{Echo, StatType};
+/// use wuwa_calculator::simulation::simulate_level_up;
+/// use wuwa_calculator::sonata::Sonata;
+///
+/// let stats = Stats::new_from_base(BaseStats {hp: 10000.0, atk: 400.0, def: 1000.0});
+/// let target = Target {
+///     element: Element::Aero,
+///     skill_type: SkillType::Basic,
+///     skill_multiplier: 1.0,
+///     skill_scaling_bonus: 1.0,
+///     scaling: Scaling::ATK,
+/// };
+/// let damage = |stats: &Stats| stats.skill_adjusted_damage_average(target, 90, Enemy::new(90));
+///
+/// let sub_stats = [
+///     (StatType::CritRate, 0.081),
+///     (StatType::CritDmg, 0.174),
+///     (StatType::None, 0.0),
+///     (StatType::None, 0.0),
+///     (StatType::None, 0.0),
+/// ];
+/// let echo = Echo::new(4, 5, Sonata::SierraGale, None, StatType::CritDmg, 10, sub_stats).unwrap();
+/// let distribution = simulate_level_up(&stats, echo, &[], 25, 1000, 1, damage).unwrap();
+/// assert!(distribution.percentile(0.0) > distribution.current);
+/// assert!(distribution.expected_gain() > 0.0);
+/// ```
+pub fn simulate_level_up(stats: &Stats, echo: Echo, others: &[Echo], target_level: isize, trials: usize, seed: u64, damage: impl Fn(&Stats) -> f64) -> Result<DamageDistribution, EchoError> {
+    let level = echo.level()?;
+    if target_level < level || Some(target_level) > max_echo_level(echo.rarity) {
+        return Err(EchoError::InvalidLevel(target_level));
+    }
+    let leveled = Echo::new(echo.cost, echo.rarity, echo.sonata, echo.identity, echo.main_stat_type, target_level, echo.sub_stats)?;
+    let rolled = echo.sub_stats.iter().filter(|(stat_type, _)| *stat_type != StatType::None).count();
+    let unlocked = (target_level / LEVELS_PER_SUB_STAT) as usize;
+
+    let mut build = [others, &[echo]].concat();
+    let build_damage = |build: &[Echo]| {
+        let mut stats = stats.clone();
+        for echo in build {
+            echo.add_to_stats(&mut stats);
+        }
+        add_set_bonuses(build, &mut stats, true);
+        damage(&stats)
+    };
+    let current = build_damage(&build);
+
+    let mut rng = Rng(seed.max(1));
+    let mut samples: Vec<f64> = (0..trials.max(1))
+        .map(|_| {
+            let mut echo = leveled;
+            for _ in rolled..unlocked {
+                roll_sub_stat(&mut echo, &mut rng);
+            }
+            *build.last_mut().unwrap() = echo;
+            build_damage(&build)
+        })
+        .collect();
+    samples.sort_by(f64::total_cmp);
+
+    Ok(DamageDistribution {
+        current,
+        samples,
+    })
+}
+
+// Rolls a stat the echo doesn't have yet into its first unrolled substat, if it has one
+fn roll_sub_stat(echo: &mut Echo, rng: &mut Rng) {
+    let Some(slot) = echo.sub_stats.iter().position(|(stat_type, _)| *stat_type == StatType::None) else {
+        return;
+    };
+    let candidates: Vec<_> = SUB_STAT_TIERS.iter()
+        .filter(|tiers| echo.sub_stats.iter().all(|(stat_type, _)| *stat_type != tiers.stat_type))
+        .collect();
+    let tiers = candidates[rng.below(candidates.len())];
+    echo.sub_stats[slot] = (tiers.stat_type, tiers.values[rng.below(tiers.values.len())]);
+}
+
+// Xorshift generator, so simulations can be reproduced from their seed
+struct Rng(u64);
+
+impl Rng {
+    fn below(&mut self, n: usize) -> usize {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 % n as u64) as usize
+    }
+}
@@ -0,0 +1,68 @@
+use crate::calculator::{BaseStats, Enemy, Scaling, Stats, Target};
+use crate::{Element, SkillType};
+use crate::echo::{Echo, EchoError, StatType, SUB_STAT_TIERS};
+use crate::simulation::simulate_level_up;
+use crate::sonata::{add_set_bonuses, Sonata};
+
+#[test]
+fn test_simulate_level_up() {
+    let stats = Stats::new_from_base(BaseStats {hp: 10000.0, atk: 400.0, def: 1000.0});
+    let target = Target {
+        element: Element::Aero,
+        skill_type: SkillType::Basic,
+        skill_multiplier: 1.0,
+        skill_scaling_bonus: 1.0,
+        scaling: Scaling::ATK,
+    };
+    let damage = |stats: &Stats| stats.skill_adjusted_damage_average(target, 90, Enemy::new(90));
+    let no_sub_stats = [(StatType::None, 0.0); 5];
+    let others = [Echo::new(3, 5, Sonata::SierraGale, None, StatType::AeroDmg, 25, no_sub_stats).unwrap()];
+    let build_damage = |echo: Echo| {
+        let mut stats = stats.clone();
+        for echo in [others[0], echo] {
+            echo.add_to_stats(&mut stats);
+        }
+        add_set_bonuses(&[others[0], echo], &mut stats, true);
+        damage(&stats)
+    };
+
+    // Without reaching a new substat, only the main and secondary stats grow
+    let echo = Echo::new(4, 5, Sonata::SierraGale, None, StatType::AtkMult, 0, no_sub_stats).unwrap();
+    let distribution = simulate_level_up(&stats, echo, &others, 4, 10, 1, damage).unwrap();
+    assert_eq!(distribution.current, build_damage(echo));
+    let leveled = Echo::new(4, 5, Sonata::SierraGale, None, StatType::AtkMult, 4, no_sub_stats).unwrap();
+    assert!(distribution.samples.iter().all(|sample| *sample == build_damage(leveled)));
+
+    // A single roll from +20 to +25 lands between its worst and best outcome
+    let sub_stats = [
+        (StatType::DefFlat, 40.0),
+        (StatType::HpFlat, 320.0),
+        (StatType::DefMult, 0.081),
+        (StatType::HpMult, 0.064),
+        (StatType::None, 0.0),
+    ];
+    let echo = Echo::new(4, 5, Sonata::SierraGale, None, StatType::AtkMult, 20, sub_stats).unwrap();
+    let distribution = simulate_level_up(&stats, echo, &others, 25, 2000, 7, damage).unwrap();
+    let with_last_sub_stat = |sub_stat| {
+        let mut sub_stats = sub_stats;
+        sub_stats[4] = sub_stat;
+        build_damage(Echo::new(4, 5, Sonata::SierraGale, None, StatType::AtkMult, 25, sub_stats).unwrap())
+    };
+    let candidates: Vec<_> = SUB_STAT_TIERS.iter()
+        .filter(|tiers| sub_stats.iter().all(|(stat_type, _)| *stat_type != tiers.stat_type))
+        .collect();
+    let (best, tiers) = candidates.iter()
+        .map(|tiers| (with_last_sub_stat((tiers.stat_type, tiers.values[tiers.values.len() - 1])), tiers.values.len()))
+        .max_by(|a, b| a.0.total_cmp(&b.0))
+        .unwrap();
+    assert!(distribution.current < distribution.percentile(0.0));
+    assert!(distribution.percentile(0.5) <= distribution.percentile(0.9));
+    assert_eq!(distribution.percentile(1.0), best);
+    // Only the highest tier of the best stat reaches it
+    let chance = distribution.chance_of_at_least(best);
+    assert!((chance - 1.0 / (candidates.len() * tiers) as f64).abs() < 0.01);
+    assert_eq!(distribution, simulate_level_up(&stats, echo, &others, 25, 2000, 7, damage).unwrap());
+
+    assert_eq!(simulate_level_up(&stats, echo, &others, 15, 10, 1, damage), Err(EchoError::InvalidLevel(15)));
+    assert_eq!(simulate_level_up(&stats, echo, &others, 30, 10, 1, damage), Err(EchoError::InvalidLevel(30)));
+}
@@ -0,0 +1,262 @@
+use crate::calculator::Stats;
+use crate::echo::{Echo, StatType};
+use crate::modifier::StatModifier;
+
+#[cfg(test)]
+mod tests;
+
+/// The `Sonata` enum represents the sonata set an echo belongs to
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Sonata {
+    None,
+    FreezingFrost,
+    MoltenRift,
+    VoidThunder,
+    SierraGale,
+    CelestialLight,
+    SunSinkingEclipse,
+    RejuvenatingGlow,
+    MoonlitClouds,
+    LingeringTunes,
+}
+
+/// The `SetEffect` struct represents a single stat bonus granted by a sonata set
+#[derive(Copy, Clone)]
+pub struct SetEffect {
+    pub stat_type: StatType,
+    /// Value of a single stack of the bonus
+    pub value: f64,
+    pub max_stacks: usize,
+    /// Whether the bonus needs something to happen in combat to be active, like casting an Intro Skill
+    pub conditional: bool,
+}
+
+impl SetEffect {
+    const fn passive(stat_type: StatType, value: f64) -> SetEffect {
+        SetEffect { stat_type, value, max_stacks: 1, conditional: false }
+    }
+
+    const fn conditional(stat_type: StatType, value: f64, max_stacks: usize) -> SetEffect {
+        SetEffect { stat_type, value, max_stacks, conditional: true }
+    }
+}
+
+/// The `SonataSet` struct holds the 2-piece and 5-piece effects of a sonata set
+pub struct SonataSet {
+    pub sonata: Sonata,
+    pub name: &'static str,
+    pub two_piece: &'static [SetEffect],
+    pub five_piece: &'static [SetEffect],
+}
+
+/// Registry of all the sonata sets. Effects that only buff other party members are not included
+pub const SONATA_SETS: [SonataSet; 9] = [
+    SonataSet {
+        sonata: Sonata::FreezingFrost,
+        name: "Freezing Frost",
+        two_piece: &[SetEffect::passive(StatType::GlacioDmg, 0.10)],
+        // Stacks on Basic Attack or Heavy Attack
+        five_piece: &[SetEffect::conditional(StatType::GlacioDmg, 0.10, 3)],
+    },
+    SonataSet {
+        sonata: Sonata::MoltenRift,
+        name: "Molten Rift",
+        two_piece: &[SetEffect::passive(StatType::FusionDmg, 0.10)],
+        // After casting a Resonance Skill
+        five_piece: &[SetEffect::conditional(StatType::FusionDmg, 0.30, 1)],
+    },
+    SonataSet {
+        sonata: Sonata::VoidThunder,
+        name: "Void Thunder",
+        two_piece: &[SetEffect::passive(StatType::ElectroDmg, 0.10)],
+        // Stacks on Heavy Attack or Resonance Skill
+        five_piece: &[SetEffect::conditional(StatType::ElectroDmg, 0.15, 2)],
+    },
+    SonataSet {
+        sonata: Sonata::SierraGale,
+        name: "Sierra Gale",
+        two_piece: &[SetEffect::passive(StatType::AeroDmg, 0.10)],
+        // After casting an Intro Skill
+        five_piece: &[SetEffect::conditional(StatType::AeroDmg, 0.30, 1)],
+    },
+    SonataSet {
+        sonata: Sonata::CelestialLight,
+        name: "Celestial Light",
+        two_piece: &[SetEffect::passive(StatType::SpectroDmg, 0.10)],
+        // After casting an Intro Skill
+        five_piece: &[SetEffect::conditional(StatType::SpectroDmg, 0.30, 1)],
+    },
+    SonataSet {
+        sonata: Sonata::SunSinkingEclipse,
+        name: "Sun-sinking Eclipse",
+        two_piece: &[SetEffect::passive(StatType::HavocDmg, 0.10)],
+        // Stacks on Basic Attack or Heavy Attack
+        five_piece: &[SetEffect::conditional(StatType::HavocDmg, 0.075, 4)],
+    },
+    SonataSet {
+        sonata: Sonata::RejuvenatingGlow,
+        name: "Rejuvenating Glow",
+        two_piece: &[SetEffect::passive(StatType::Healing, 0.10)],
+        // After healing a party member, for the whole party
+        five_piece: &[SetEffect::conditional(StatType::AtkMult, 0.15, 1)],
+    },
+    SonataSet {
+        sonata: Sonata::MoonlitClouds,
+        name: "Moonlit Clouds",
+        two_piece: &[SetEffect::passive(StatType::EnergyRegen, 0.10)],
+        // The 5-piece ATK bonus only applies to the next Resonator
+        five_piece: &[],
+    },
+    SonataSet {
+        sonata: Sonata::LingeringTunes,
+        name: "Lingering Tunes",
+        two_piece: &[SetEffect::passive(StatType::AtkMult, 0.10)],
+        // Stacks while on the field
+        five_piece: &[SetEffect::conditional(StatType::AtkMult, 0.05, 4)],
+    },
+];
+
+impl Sonata {
+    /// Returns the set effects of the sonata, or `None` for echoes without a sonata
+    pub fn set(&self) -> Option<&'static SonataSet> {
+        SONATA_SETS.iter().find(|set| set.sonata == *self)
+    }
+}
+
+fn for_each_set_effect(echoes: &[Echo], conditional: bool, mut f: impl FnMut(StatModifier)) {
+    let mut pieces = [0usize; SONATA_SETS.len()];
+    for (i, echo) in echoes.iter().enumerate() {
+        // Copies of the same monster in a set only count as one piece
+        if echoes[..i].iter().any(|other| other.sonata == echo.sonata && other.same_monster(echo)) {
+            continue;
+        }
+        if let Some(index) = set_index(echo.sonata) {
+            pieces[index] += 1;
+        }
+    }
+
+    for (set, count) in SONATA_SETS.iter().zip(pieces) {
+        let active: &[&[SetEffect]] = match count {
+            0..=1 => &[],
+            2..=4 => &[set.two_piece],
+            _ => &[set.two_piece, set.five_piece],
+        };
+        for effect in active.iter().flat_map(|effects| effects.iter()) {
+            if !effect.conditional {
+                f(StatModifier::add(effect.stat_type, effect.value));
+            } else if conditional {
+                f(StatModifier::add(effect.stat_type, effect.value * effect.max_stacks as f64));
+            }
+        }
+    }
+}
+
+/// Returns the modifiers the set bonuses of a build apply to a character, like `add_set_bonuses` does
+pub fn set_bonus_modifiers(echoes: &[Echo], conditional: bool) -> Vec<StatModifier> {
+    let mut modifiers = Vec::new();
+    for_each_set_effect(echoes, conditional, |modifier| modifiers.push(modifier));
+    modifiers
+}
+
+/// Adds the set bonuses of a build to the stats of a character. If `conditional` is set, conditional
+/// effects are assumed to be active at their maximum stacks. Echoes of the same monster only count once
+///
+/// # Examples
+/// ```
+/// use wuwa_calculator::calculator::{BaseStats, Stats};
+/// use wuwa_calculator::echo::{Echo, StatType};
+/// use wuwa_calculator::sonata::{add_set_bonuses, Sonata};
+///
+/// let mut stats = Stats::new_from_base(BaseStats {hp: 7954.0, atk: 343.0, def: 899.0});
+///
+/// let echo = Echo {
+///   cost: 1,
+///   rarity: 5,
+///   sonata: Sonata::LingeringTunes,
+///   identity: None,
+///   main_stat_type: StatType::AtkMult,
+///   main_stat_value: 0.18,
+///   secondary_stat_type: StatType::HpFlat,
+///   secondary_stat_value: 2280.0,
+///   sub_stats: [(StatType::None, 0.0); 5],
+/// };
+/// add_set_bonuses(&[echo, echo], &mut stats, true);
+/// assert_eq!(stats.atk_mult, 1.1);
+/// ```
+pub fn add_set_bonuses(echoes: &[Echo], stats: &mut Stats, conditional: bool) {
+    for_each_set_effect(echoes, conditional, |modifier| modifier.apply(stats));
+}
+
+/// Removes the set bonuses of a build from the stats of a character
+pub fn remove_set_bonuses(echoes: &[Echo], stats: &mut Stats, conditional: bool) {
+    for_each_set_effect(echoes, conditional, |modifier| modifier.revert(stats));
+}
+
+/// The largest bonuses combinations of set effects can give, for bounding what a build's set bonuses can be
+pub(crate) struct SetBonusBounds {
+    // Total bonus of the 2-piece and the 5-piece effects of each set in `SONATA_SETS`, for each stat type in
+    // `StatType::ALL`
+    two_pieces: [[f64; StatType::ALL.len()]; SONATA_SETS.len()],
+    five_pieces: [[f64; StatType::ALL.len()]; SONATA_SETS.len()],
+    // Stat types any set effect increases
+    stats: Vec<usize>,
+}
+
+impl SetBonusBounds {
+    pub(crate) fn new(conditional: bool) -> SetBonusBounds {
+        let mut two_pieces = [[0.0; StatType::ALL.len()]; SONATA_SETS.len()];
+        let mut five_pieces = [[0.0; StatType::ALL.len()]; SONATA_SETS.len()];
+        for (i, set) in SONATA_SETS.iter().enumerate() {
+            for (effects, totals) in [(set.two_piece, &mut two_pieces[i]), (set.five_piece, &mut five_pieces[i])] {
+                for effect in effects {
+                    if !effect.conditional {
+                        totals[effect.stat_type as usize] += effect.value;
+                    } else if conditional {
+                        totals[effect.stat_type as usize] += effect.value * effect.max_stacks as f64;
+                    }
+                }
+            }
+        }
+        let stats = (0..StatType::ALL.len())
+            .filter(|&stat| two_pieces.iter().chain(&five_pieces).any(|totals| totals[stat] > 0.0))
+            .collect();
+
+        SetBonusBounds {
+            two_pieces,
+            five_pieces,
+            stats,
+        }
+    }
+
+    /// Returns, for each stat type in `StatType::ALL`, the largest bonus any combination of set effects can give to
+    /// it, given the most pieces of each set in `SONATA_SETS` a build could have
+    pub(crate) fn max_bonuses(&self, max_pieces: &[usize; SONATA_SETS.len()]) -> [f64; StatType::ALL.len()] {
+        // Five echoes can complete either a single 5-piece set or two 2-piece sets
+        let mut max_bonuses = [0.0f64; StatType::ALL.len()];
+        for &stat in &self.stats {
+            let mut best_two_pieces = [0.0f64; 2];
+            let mut best_five_piece = 0.0f64;
+            for (i, pieces) in max_pieces.iter().enumerate() {
+                if *pieces < 2 {
+                    continue;
+                }
+                let value = self.two_pieces[i][stat];
+                if value > best_two_pieces[0] {
+                    best_two_pieces = [value, best_two_pieces[0]];
+                } else if value > best_two_pieces[1] {
+                    best_two_pieces[1] = value;
+                }
+                if *pieces >= 5 {
+                    best_five_piece = best_five_piece.max(value + self.five_pieces[i][stat]);
+                }
+            }
+            max_bonuses[stat] = (best_two_pieces[0] + best_two_pieces[1]).max(best_five_piece);
+        }
+        max_bonuses
+    }
+}
+
+/// Returns the index in `SONATA_SETS` of the set of a sonata, or `None` for echoes without a sonata
+pub(crate) fn set_index(sonata: Sonata) -> Option<usize> {
+    SONATA_SETS.iter().position(|set| set.sonata == sonata)
+}
@@ -0,0 +1,34 @@
+use crate::calculator::{BaseStats, Stats};
+use crate::echo::{Echo, EchoClass, EchoIdentity, StatType};
+use crate::sonata::{add_set_bonuses, Sonata};
+
+#[test]
+fn test_duplicate_echoes_dont_stack_set_bonuses() {
+    let base_stats = BaseStats {hp: 10000.0, atk: 400.0, def: 1000.0};
+    let identity = |id| EchoIdentity {id, class: EchoClass::Common, skill: None};
+    let echo = Echo {
+        cost: 1,
+        rarity: 5,
+        sonata: Sonata::LingeringTunes,
+        identity: Some(identity(1)),
+        main_stat_type: StatType::AtkMult,
+        main_stat_value: 0.18,
+        secondary_stat_type: StatType::HpFlat,
+        secondary_stat_value: 2280.0,
+        sub_stats: [(StatType::None, 0.0); 5],
+    };
+
+    let mut stats = Stats::new_from_base(base_stats);
+    add_set_bonuses(&[echo, echo], &mut stats, false);
+    assert_eq!(stats.atk_mult, 1.0);
+
+    let other = Echo { identity: Some(identity(2)), ..echo };
+    add_set_bonuses(&[echo, echo, other], &mut stats, false);
+    assert!((stats.atk_mult - 1.1).abs() < 1e-12);
+
+    // Echoes without an identity are never copies of each other
+    let mut stats = Stats::new_from_base(base_stats);
+    let unnamed = Echo { identity: None, ..echo };
+    add_set_bonuses(&[unnamed, unnamed], &mut stats, false);
+    assert!((stats.atk_mult - 1.1).abs() < 1e-12);
+}
@@ -0,0 +1,185 @@
+use crate::calculator::Stats;
+use crate::echo::StatType;
+use crate::level_breakpoints;
+use crate::modifier::StatModifier;
+
+#[cfg(test)]
+mod tests;
+
+/// The `WeaponLevel` struct holds the base ATK and secondary stat of a weapon at a level breakpoint.
+/// Stats between two breakpoints of the same ascension are interpolated
+#[derive(Copy, Clone)]
+pub struct WeaponLevel {
+    pub level: isize,
+    pub ascension: usize,
+    pub base_atk: f64,
+    pub secondary_stat_value: f64,
+}
+
+/// The `PassiveEffect` struct represents a single stat bonus granted by a weapon's passive
+#[derive(Copy, Clone)]
+pub struct PassiveEffect {
+    pub stat_type: StatType,
+    /// Value of a single stack of the bonus for each rank, from rank 1 to rank 5
+    pub values: [f64; 5],
+    pub max_stacks: usize,
+    /// Whether the bonus needs something to happen in combat to be active, like casting a Resonance Liberation
+    pub conditional: bool,
+}
+
+/// The `WeaponData` struct holds everything about a weapon that doesn't depend on its level or rank
+pub struct WeaponData {
+    pub name: &'static str,
+    pub secondary_stat_type: StatType,
+    pub levels: &'static [WeaponLevel],
+    pub passive: &'static [PassiveEffect],
+}
+
+// Base ATK grows by the same amount at every level and at every ascension, and secondary stats grow linearly from
+// level 1 to 4.5 times as much at level 90. Verdant Summit at 60/3 and Helios Cleaver at 70/4 are from the game
+pub const VERDANT_SUMMIT: WeaponData = WeaponData {
+    name: "Verdant Summit",
+    secondary_stat_type: StatType::CritDmg,
+    levels: &[
+        WeaponLevel {level: 1, ascension: 0, base_atk: 47.0, secondary_stat_value: 0.108},
+        WeaponLevel {level: 20, ascension: 0, base_atk: 122.0, secondary_stat_value: 0.189},
+        WeaponLevel {level: 20, ascension: 1, base_atk: 153.0, secondary_stat_value: 0.189},
+        WeaponLevel {level: 40, ascension: 1, base_atk: 232.0, secondary_stat_value: 0.274},
+        WeaponLevel {level: 40, ascension: 2, base_atk: 264.0, secondary_stat_value: 0.274},
+        WeaponLevel {level: 50, ascension: 2, base_atk: 303.0, secondary_stat_value: 0.316},
+        WeaponLevel {level: 50, ascension: 3, base_atk: 335.0, secondary_stat_value: 0.316},
+        WeaponLevel {level: 60, ascension: 3, base_atk: 374.0, secondary_stat_value: 0.359},
+        WeaponLevel {level: 60, ascension: 4, base_atk: 406.0, secondary_stat_value: 0.359},
+        WeaponLevel {level: 70, ascension: 4, base_atk: 445.0, secondary_stat_value: 0.401},
+        WeaponLevel {level: 70, ascension: 5, base_atk: 477.0, secondary_stat_value: 0.401},
+        WeaponLevel {level: 80, ascension: 5, base_atk: 516.0, secondary_stat_value: 0.444},
+        WeaponLevel {level: 80, ascension: 6, base_atk: 548.0, secondary_stat_value: 0.444},
+        WeaponLevel {level: 90, ascension: 6, base_atk: 587.0, secondary_stat_value: 0.486},
+    ],
+    passive: &[
+        PassiveEffect {
+            stat_type: StatType::AllElementDmg,
+            values: [0.12, 0.15, 0.18, 0.21, 0.24],
+            max_stacks: 1,
+            conditional: false,
+        },
+        // Stacks on Intro Skill or Resonance Liberation
+        PassiveEffect {
+            stat_type: StatType::HeavyDmg,
+            values: [0.24, 0.30, 0.36, 0.42, 0.48],
+            max_stacks: 2,
+            conditional: true,
+        },
+    ],
+};
+
+pub const HELIOS_CLEAVER: WeaponData = WeaponData {
+    name: "Helios Cleaver",
+    secondary_stat_type: StatType::AtkMult,
+    levels: &[
+        WeaponLevel {level: 1, ascension: 0, base_atk: 33.0, secondary_stat_value: 0.068},
+        WeaponLevel {level: 20, ascension: 0, base_atk: 85.0, secondary_stat_value: 0.118},
+        WeaponLevel {level: 20, ascension: 1, base_atk: 108.0, secondary_stat_value: 0.118},
+        WeaponLevel {level: 40, ascension: 1, base_atk: 163.0, secondary_stat_value: 0.171},
+        WeaponLevel {level: 40, ascension: 2, base_atk: 185.0, secondary_stat_value: 0.171},
+        WeaponLevel {level: 50, ascension: 2, base_atk: 213.0, secondary_stat_value: 0.198},
+        WeaponLevel {level: 50, ascension: 3, base_atk: 235.0, secondary_stat_value: 0.198},
+        WeaponLevel {level: 60, ascension: 3, base_atk: 263.0, secondary_stat_value: 0.224},
+        WeaponLevel {level: 60, ascension: 4, base_atk: 285.0, secondary_stat_value: 0.224},
+        WeaponLevel {level: 70, ascension: 4, base_atk: 312.0, secondary_stat_value: 0.251},
+        WeaponLevel {level: 70, ascension: 5, base_atk: 335.0, secondary_stat_value: 0.251},
+        WeaponLevel {level: 80, ascension: 5, base_atk: 362.0, secondary_stat_value: 0.277},
+        WeaponLevel {level: 80, ascension: 6, base_atk: 384.0, secondary_stat_value: 0.277},
+        WeaponLevel {level: 90, ascension: 6, base_atk: 412.0, secondary_stat_value: 0.304},
+    ],
+    passive: &[
+        // Stacks after casting a Resonance Liberation
+        PassiveEffect {
+            stat_type: StatType::AtkMult,
+            values: [0.03, 0.0375, 0.045, 0.0525, 0.06],
+            max_stacks: 4,
+            conditional: true,
+        },
+    ],
+};
+
+/// The `Weapon` struct represents a weapon at a given level and rank that can be equipped by a character
+#[derive(Copy, Clone)]
+pub struct Weapon {
+    pub data: &'static WeaponData,
+    pub level: isize,
+    pub rank: usize,
+    pub base_atk: f64,
+    pub secondary_stat_value: f64,
+}
+
+impl Weapon {
+    /// Creates a new `Weapon` from its data. Returns `None` if the rank is not between 1 and 5, or if the data has
+    /// no breakpoints for the given level and ascension
+    ///
+    /// # Examples
+    /// ```
+    /// use wuwa_calculator::weapon::{Weapon, VERDANT_SUMMIT};
+    ///
+    /// let weapon = Weapon::new(&VERDANT_SUMMIT, 60, 3, 1).unwrap();
+    /// assert_eq!(weapon.base_atk, 374.0);
+    /// assert!(Weapon::new(&VERDANT_SUMMIT, 60, 3, 6).is_none());
+    /// ```
+    pub fn new(data: &'static WeaponData, level: isize, ascension: usize, rank: usize) -> Option<Weapon> {
+        if !(1..=5).contains(&rank) {
+            return None;
+        }
+        let (lower, upper, t) = level_breakpoints(data.levels, level, ascension, |point| (point.level, point.ascension))?;
+        Some(Weapon {
+            data,
+            level,
+            rank,
+            base_atk: lower.base_atk + (upper.base_atk - lower.base_atk) * t,
+            secondary_stat_value: lower.secondary_stat_value + (upper.secondary_stat_value - lower.secondary_stat_value) * t,
+        })
+    }
+
+    /// Returns the modifiers the weapon applies to a character: its base ATK, secondary stat and passive effects. If
+    /// `conditional` is set, conditional passive effects are included at their maximum stacks
+    pub fn modifiers(&self, conditional: bool) -> impl Iterator<Item = StatModifier> + '_ {
+        let passive = self.data.passive.iter()
+            .filter(move |effect| conditional || !effect.conditional)
+            .map(|effect| {
+                let stacks = if effect.conditional { effect.max_stacks } else { 1 };
+                StatModifier::add(effect.stat_type, effect.values[self.rank - 1] * stacks as f64)
+            });
+        [
+            StatModifier::add(StatType::BaseAtk, self.base_atk),
+            StatModifier::add(self.data.secondary_stat_type, self.secondary_stat_value),
+        ].into_iter().chain(passive)
+    }
+
+    /// Adds the stats of the weapon to the stats of a character. If `conditional` is set, conditional passive
+    /// effects are assumed to be active at their maximum stacks
+    ///
+    /// # Examples
+    /// ```
+    /// use wuwa_calculator::calculator::{BaseStats, Stats};
+    /// use wuwa_calculator::weapon::{Weapon, VERDANT_SUMMIT};
+    ///
+    /// let mut stats = Stats::new_from_base(BaseStats {hp: 7954.0, atk: 343.0, def: 899.0});
+    ///
+    /// let weapon = Weapon::new(&VERDANT_SUMMIT, 60, 3, 1).unwrap();
+    /// weapon.add_to_stats(&mut stats, false);
+    /// assert_eq!(stats.base_atk, 343.0 + 374.0);
+    /// assert_eq!(stats.crit_dmg, 1.5 + 0.359);
+    /// assert_eq!(stats.element_dmg, [0.12; 6]);
+    /// ```
+    pub fn add_to_stats(&self, stats: &mut Stats, conditional: bool) {
+        for modifier in self.modifiers(conditional) {
+            modifier.apply(stats);
+        }
+    }
+
+    /// Removes the stats of the weapon from the stats of a character
+    pub fn remove_from_stats(&self, stats: &mut Stats, conditional: bool) {
+        for modifier in self.modifiers(conditional) {
+            modifier.revert(stats);
+        }
+    }
+}
@@ -0,0 +1,32 @@
+use crate::weapon::{Weapon, HELIOS_CLEAVER, VERDANT_SUMMIT};
+
+#[test]
+fn test_weapon_levels() {
+    // Ascension ranks cap the level at 20, 40, 50, 60, 70, 80 and 90
+    const LEVEL_CAPS: [isize; 7] = [20, 40, 50, 60, 70, 80, 90];
+    for data in [&VERDANT_SUMMIT, &HELIOS_CLEAVER] {
+        let mut previous = Weapon::new(data, 1, 0, 1).unwrap();
+        for (ascension, cap) in LEVEL_CAPS.iter().enumerate() {
+            let lowest = if ascension == 0 { 1 } else { LEVEL_CAPS[ascension - 1] };
+            for level in lowest..=*cap {
+                let weapon = Weapon::new(data, level, ascension, 1).unwrap();
+                assert!(weapon.base_atk >= previous.base_atk);
+                assert!(weapon.secondary_stat_value >= previous.secondary_stat_value);
+                previous = weapon;
+            }
+        }
+        assert!(Weapon::new(data, 91, 6, 1).is_none());
+        assert!(Weapon::new(data, 30, 0, 1).is_none());
+    }
+
+    // Values from the game
+    let weapon = Weapon::new(&HELIOS_CLEAVER, 70, 4, 1).unwrap();
+    assert_eq!((weapon.base_atk, weapon.secondary_stat_value), (312.0, 0.251));
+    let weapon = Weapon::new(&VERDANT_SUMMIT, 60, 3, 1).unwrap();
+    assert_eq!((weapon.base_atk, weapon.secondary_stat_value), (374.0, 0.359));
+
+    // Levels between breakpoints are interpolated, and ascending raises the base ATK at the same level
+    let weapon = Weapon::new(&VERDANT_SUMMIT, 55, 3, 1).unwrap();
+    assert!((weapon.base_atk - (335.0 + 374.0) / 2.0).abs() < 1e-9);
+    assert!(Weapon::new(&VERDANT_SUMMIT, 60, 4, 1).unwrap().base_atk > weapon.base_atk);
+}